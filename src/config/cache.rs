@@ -30,6 +30,10 @@ impl Default for CacheConfig {
 }
 
 impl CacheConfig {
+    pub fn block_cache_size(&self) -> u64 {
+        self.block_cache_size
+    }
+
     pub fn validate(&self) -> Result<(), CacheConfigErrors> {
         let mut err = CacheConfigErrors::new();
 
@@ -7,6 +7,13 @@ pub enum CompactionStrategy {
     Hybrid,
 }
 
+// Current shape of one level, as seen by the compaction picker
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LevelSize {
+    pub num_files: usize,
+    pub total_bytes: u64,
+}
+
 #[derive(Debug)]
 pub struct CompactionConfig {
     compaction_strategy: CompactionStrategy,
@@ -15,6 +22,7 @@ pub struct CompactionConfig {
     l0_file_count_compaction_trigger: u8,
     max_bytes_for_level_base: u64,
     target_file_size_base: u64,
+    target_file_size_multiplier: u8,
 }
 
 const DEFAULT_LEVEL_SIZE_MULITPLIER: u8 = 10;
@@ -22,6 +30,7 @@ const DEFAULT_MAX_LEVELS: u8 = 7;
 const DEFAULT_LEVEL_0_FILE_COUNT_COMPACTION_TRIGGER: u8 = 10;
 const DEFAULT_MAX_BYTES_FOR_LEVEL_BASE: u64 = 512 * 1024 * 1024;
 const DEFAULT_TARGET_FILE_SIZE_BASE: u64 = 64 * 1024 * 1024;
+const DEFAULT_TARGET_FILE_SIZE_MULTIPLIER: u8 = 1;
 
 impl Default for CompactionConfig {
     fn default() -> Self {
@@ -32,11 +41,60 @@ impl Default for CompactionConfig {
             l0_file_count_compaction_trigger: DEFAULT_LEVEL_0_FILE_COUNT_COMPACTION_TRIGGER,
             max_bytes_for_level_base: DEFAULT_MAX_BYTES_FOR_LEVEL_BASE,
             target_file_size_base: DEFAULT_TARGET_FILE_SIZE_BASE,
+            target_file_size_multiplier: DEFAULT_TARGET_FILE_SIZE_MULTIPLIER,
         }
     }
 }
 
 impl CompactionConfig {
+    // L0 is compacted on file count (l0_file_count_compaction_trigger) so it has no byte target,
+    // level L >= 1 targets max_bytes_for_level_base * level_size_multiplier^(L-1)
+    pub fn target_size_for_level(&self, level: u8) -> u64 {
+        if level == 0 {
+            return 0;
+        }
+        (self.level_size_muliplier as u64)
+            .saturating_pow(level as u32 - 1)
+            .saturating_mul(self.max_bytes_for_level_base)
+    }
+
+    // L0 and L1 files use target_file_size_base, deeper levels scale by
+    // target_file_size_multiplier^(L-1)
+    pub fn target_file_size_for_level(&self, level: u8) -> u64 {
+        if level == 0 {
+            return self.target_file_size_base;
+        }
+        (self.target_file_size_multiplier as u64)
+            .saturating_pow(level as u32 - 1)
+            .saturating_mul(self.target_file_size_base)
+    }
+
+    // L0 is scored by file count against its trigger, other levels by bytes against their target.
+    // A score >= 1.0 means the level needs compacting.
+    pub fn compaction_score(&self, level: u8, size: &LevelSize) -> f64 {
+        if level == 0 {
+            return size.num_files as f64 / self.l0_file_count_compaction_trigger as f64;
+        }
+        size.total_bytes as f64 / self.target_size_for_level(level) as f64
+    }
+
+    // levels[i] describes level i. Returns the highest scoring level that needs compacting, the
+    // lower level on ties. The last level has nowhere to compact into so it is never picked.
+    pub fn pick_level(&self, levels: &[LevelSize]) -> Option<u8> {
+        let last_level = self.max_levels.saturating_sub(1) as usize;
+        let mut best: Option<(u8, f64)> = None;
+
+        for (level, size) in levels.iter().enumerate().take(last_level) {
+            let level = level as u8;
+            let score = self.compaction_score(level, size);
+            if score >= 1.0 && best.is_none_or(|(_, best_score)| score > best_score) {
+                best = Some((level, score));
+            }
+        }
+
+        best.map(|(level, _)| level)
+    }
+
     pub fn validate(&self) -> Result<(), CompactionConfigErrors> {
         let mut cce = CompactionConfigErrors::new();
         if self.level_size_muliplier < 2 {
@@ -67,6 +125,11 @@ impl CompactionConfig {
             ));
         }
 
+        if self.target_file_size_multiplier == 0 {
+            cce.errors
+                .push(CompactionConfigError::TargetFileSizeMultiplierZero);
+        }
+
         if !self
             .max_bytes_for_level_base
             .is_multiple_of(self.target_file_size_base)
@@ -83,3 +146,117 @@ impl CompactionConfig {
         Err(cce)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MB: u64 = 1024 * 1024;
+
+    #[test]
+    fn test_target_size_for_level_defaults() {
+        let config = CompactionConfig::default();
+
+        assert_eq!(config.target_size_for_level(0), 0);
+        assert_eq!(config.target_size_for_level(1), 512 * MB);
+        assert_eq!(config.target_size_for_level(2), 5120 * MB);
+        assert_eq!(config.target_size_for_level(3), 51200 * MB);
+        assert_eq!(config.target_size_for_level(6), 51_200_000 * MB);
+    }
+
+    #[test]
+    fn test_target_size_for_level_saturates() {
+        let config = CompactionConfig {
+            level_size_muliplier: 255,
+            ..CompactionConfig::default()
+        };
+
+        assert_eq!(config.target_size_for_level(2), 255 * 512 * MB);
+        assert_eq!(config.target_size_for_level(10), u64::MAX);
+        assert_eq!(config.target_size_for_level(u8::MAX), u64::MAX);
+    }
+
+    #[test]
+    fn test_target_file_size_for_level() {
+        let config = CompactionConfig::default();
+        for level in 0..=config.max_levels {
+            assert_eq!(config.target_file_size_for_level(level), 64 * MB);
+        }
+
+        let config = CompactionConfig {
+            target_file_size_multiplier: 2,
+            ..CompactionConfig::default()
+        };
+        assert_eq!(config.target_file_size_for_level(0), 64 * MB);
+        assert_eq!(config.target_file_size_for_level(1), 64 * MB);
+        assert_eq!(config.target_file_size_for_level(2), 128 * MB);
+        assert_eq!(config.target_file_size_for_level(4), 512 * MB);
+        assert_eq!(config.target_file_size_for_level(u8::MAX), u64::MAX);
+    }
+
+    fn level(num_files: usize, total_bytes: u64) -> LevelSize {
+        LevelSize {
+            num_files,
+            total_bytes,
+        }
+    }
+
+    #[test]
+    fn test_compaction_score() {
+        let config = CompactionConfig::default();
+
+        assert_eq!(config.compaction_score(0, &level(5, 0)), 0.5);
+        assert_eq!(config.compaction_score(0, &level(20, 0)), 2.0);
+        assert_eq!(config.compaction_score(1, &level(1, 256 * MB)), 0.5);
+        assert_eq!(config.compaction_score(2, &level(1, 15360 * MB)), 3.0);
+    }
+
+    #[test]
+    fn test_pick_level_chooses_highest_score() {
+        let config = CompactionConfig::default();
+
+        let levels = [
+            level(15, 0),         // 1.5
+            level(4, 1024 * MB),  // 2.0
+            level(9, 15360 * MB), // 3.0
+            level(1, 51200 * MB), // 1.0
+        ];
+
+        assert_eq!(config.pick_level(&levels), Some(2));
+    }
+
+    #[test]
+    fn test_pick_level_prefers_lower_level_on_tie() {
+        let config = CompactionConfig::default();
+
+        let levels = [level(20, 0), level(4, 1024 * MB)];
+
+        assert_eq!(config.pick_level(&levels), Some(0));
+    }
+
+    #[test]
+    fn test_pick_level_none_when_within_targets() {
+        let config = CompactionConfig::default();
+
+        let levels = [
+            level(9, 0),
+            level(8, 512 * MB - 1),
+            level(80, 5120 * MB - 1),
+        ];
+
+        assert_eq!(config.pick_level(&levels), None);
+        assert_eq!(config.pick_level(&[]), None);
+    }
+
+    #[test]
+    fn test_pick_level_skips_last_level() {
+        let config = CompactionConfig {
+            max_levels: 3,
+            ..CompactionConfig::default()
+        };
+
+        let levels = [level(0, 0), level(0, 0), level(100, u64::MAX)];
+
+        assert_eq!(config.pick_level(&levels), None);
+    }
+}
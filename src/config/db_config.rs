@@ -0,0 +1,178 @@
+use std::error::Error;
+
+use crate::{
+    config::{
+        cache::CacheConfig, compaction::CompactionConfig, mvcc::MvccConfig,
+        performance::PerformanceConfig, tconfig::TaurusConfig,
+    },
+    errors::config_errors::CrossConfigError,
+};
+
+// How far the WAL rotation size may drift from the memtable size in either direction
+const WAL_MEMTABLE_MAX_RATIO: u64 = 8;
+
+#[derive(Default)]
+pub struct Config {
+    pub taurus: TaurusConfig,
+    pub cache: CacheConfig,
+    pub compaction: CompactionConfig,
+    pub mvcc: MvccConfig,
+    pub performance: PerformanceConfig,
+}
+
+impl Config {
+    // Runs every sub-config's validation and reports each individual problem,
+    // rather than stopping at the first config that fails.
+    pub fn validate(&self) -> Result<(), Vec<Box<dyn Error>>> {
+        let mut errors: Vec<Box<dyn Error>> = Vec::new();
+
+        if let Err(e) = self.taurus.validate() {
+            errors.extend(e.errors.into_iter().map(|e| Box::new(e) as Box<dyn Error>));
+        }
+        if let Err(e) = self.cache.validate() {
+            errors.extend(e.errors.into_iter().map(|e| Box::new(e) as Box<dyn Error>));
+        }
+        if let Err(e) = self.compaction.validate() {
+            errors.extend(e.errors.into_iter().map(|e| Box::new(e) as Box<dyn Error>));
+        }
+        if let Err(e) = self.mvcc.validate() {
+            errors.extend(e.errors.into_iter().map(|e| Box::new(e) as Box<dyn Error>));
+        }
+        if let Err(e) = self.performance.validate() {
+            errors.extend(e.errors.into_iter().map(|e| Box::new(e) as Box<dyn Error>));
+        }
+
+        let wal_max_size = self.performance.wal_max_size as u64;
+        let mem_table_size = self.taurus.mem_table_size();
+        if wal_max_size.saturating_mul(WAL_MEMTABLE_MAX_RATIO) < mem_table_size
+            || mem_table_size.saturating_mul(WAL_MEMTABLE_MAX_RATIO) < wal_max_size
+        {
+            errors.push(Box::new(CrossConfigError::WalSizeMemtableMismatch(
+                self.performance.wal_max_size,
+                mem_table_size,
+            )));
+        }
+
+        if self.performance.readahead_size as u64 > self.cache.block_cache_size() {
+            errors.push(Box::new(CrossConfigError::ReadaheadExceedsBlockCache(
+                self.performance.readahead_size,
+                self.cache.block_cache_size(),
+            )));
+        }
+
+        if self.performance.wal_max_replay_size < self.performance.wal_max_size {
+            errors.push(Box::new(CrossConfigError::WalReplayLimitBelowWalSize(
+                self.performance.wal_max_replay_size,
+                self.performance.wal_max_size,
+            )));
+        }
+
+        if errors.is_empty() {
+            return Ok(());
+        }
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_config_is_valid() {
+        assert!(Config::default().validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_reports_every_error() {
+        let mut config = Config::default();
+
+        // Two MVCC violations, two performance violations, and the oversized
+        // readahead also exceeds the block cache
+        config.mvcc.snapshot_retention.min_snapshots = 20_000;
+        config.mvcc.snapshot_retention.max_snapshots = 20_000;
+        config.performance.wal_preallocate_size = 2 * 1024 * 1024 * 1024;
+        config.performance.readahead_size = 128 * 1024 * 1024;
+
+        let errors = config.validate().unwrap_err();
+        let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+
+        assert_eq!(errors.len(), 5, "unexpected errors: {messages:?}");
+        assert_eq!(
+            messages
+                .iter()
+                .filter(|m| m.starts_with("Mvcc Config Err"))
+                .count(),
+            2
+        );
+        assert_eq!(
+            messages
+                .iter()
+                .filter(|m| m.starts_with("Performance Config Err"))
+                .count(),
+            2
+        );
+        assert_eq!(
+            messages
+                .iter()
+                .filter(|m| m.starts_with("Cross Config Err"))
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_wal_much_smaller_than_memtable() {
+        let mut config = Config::default();
+        config.performance.wal_max_size = 1024 * 1024;
+
+        let errors = config.validate().unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].to_string().contains("wal max size"));
+    }
+
+    #[test]
+    fn test_wal_much_larger_than_memtable() {
+        let mut config = Config::default();
+        config.performance.wal_max_size = 1024 * 1024 * 1024;
+
+        let errors = config.validate().unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].to_string().contains("wal max size"));
+    }
+
+    #[test]
+    fn test_wal_within_ratio_of_memtable() {
+        let mut config = Config::default();
+
+        config.performance.wal_max_size = 8 * 1024 * 1024;
+        assert!(config.validate().is_ok());
+
+        config.performance.wal_max_size = 512 * 1024 * 1024;
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_readahead_exceeds_block_cache() {
+        let mut config = Config::default();
+        config.performance.readahead_size = 48 * 1024 * 1024;
+
+        let errors = config.validate().unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].to_string().contains("exceeds block cache size"));
+    }
+
+    #[test]
+    fn test_wal_replay_limit_below_wal_size() {
+        let mut config = Config::default();
+        config.performance.wal_max_replay_size = config.performance.wal_max_size - 1;
+
+        let errors = config.validate().unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].to_string().contains("wal max replay size"));
+    }
+}
@@ -1,5 +1,6 @@
 pub mod cache;
 pub mod compaction;
+pub mod db_config;
 pub mod mvcc;
 pub mod performance;
 pub mod tconfig;
@@ -8,6 +8,9 @@ pub const DEFAULT_PERIODIC_INTERVALS_MS: u64 = 1000;
 pub const DEFAULT_MAX_READ_THREADS: usize = 8;
 pub const DEFAULT_MAX_WRITE_THREADS: usize = 4;
 pub const DEFAULT_SCAN_PARALLELISM: usize = 2;
+pub const DEFAULT_WAL_PREALLOCATE_SIZE: usize = 0;
+pub const DEFAULT_WAL_MAX_SIZE: usize = 128 * 1024 * 1024;
+pub const DEFAULT_WAL_MAX_REPLAY_SIZE: usize = 1024 * 1024 * 1024;
 
 pub enum WalSyncMode {
     EveryWrite,
@@ -54,6 +57,12 @@ pub struct PerformanceConfig {
     pub wal_sync: WalSyncConfig,
     pub readahead_size: usize,
     pub parallelism: ParallelismConfig,
+    // 0 disables preallocation
+    pub wal_preallocate_size: usize,
+    // WAL is rotated once it grows past this size
+    pub wal_max_size: usize,
+    // Recovery aborts rather than replay a WAL larger than this, unless forced
+    pub wal_max_replay_size: usize,
 }
 
 impl Default for PerformanceConfig {
@@ -63,6 +72,9 @@ impl Default for PerformanceConfig {
             wal_sync: WalSyncConfig::default(),
             readahead_size: DEFAULT_READAHEAD_SIZE,
             parallelism: ParallelismConfig::default(),
+            wal_preallocate_size: DEFAULT_WAL_PREALLOCATE_SIZE,
+            wal_max_size: DEFAULT_WAL_MAX_SIZE,
+            wal_max_replay_size: DEFAULT_WAL_MAX_REPLAY_SIZE,
         }
     }
 }
@@ -85,6 +97,13 @@ impl PerformanceConfig {
                 ));
         }
 
+        if self.wal_preallocate_size > 1024 * 1024 * 1024 {
+            err.errors
+                .push(PerformanceConfigError::WalPreallocateSizeTooHigh(
+                    self.wal_preallocate_size,
+                ));
+        }
+
         match self.wal_sync.mode {
             WalSyncMode::Batch => {
                 if self.wal_sync.batch_size == 0 {
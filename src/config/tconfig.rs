@@ -11,6 +11,9 @@ pub enum CompressionType {
 const BLOCK_SIZE: u64 = 32 * 1024;
 const MEMTABLE_SIZE: u64 = 64 * 1024 * 1024;
 const BLOOM_BITS_PER_KEY: u8 = 10;
+pub const DEFAULT_MAX_KEY_SIZE: u64 = 64 * 1024;
+pub const DEFAULT_MAX_VALUE_SIZE: u64 = 256 * 1024 * 1024;
+const DEFAULT_MAX_IMMUTABLE_MEMTABLES: usize = 2;
 
 #[derive(Debug)]
 pub struct TaurusConfig {
@@ -18,6 +21,10 @@ pub struct TaurusConfig {
     mem_table_size: u64,
     compression_algo: CompressionType,
     bloom_bits_per_key: u8,
+    max_key_size: u64,
+    max_value_size: u64,
+    // Writes stall once this many frozen memtables are waiting to be flushed
+    max_immutable_memtables: usize,
 }
 
 impl Default for TaurusConfig {
@@ -27,11 +34,30 @@ impl Default for TaurusConfig {
             mem_table_size: MEMTABLE_SIZE,
             compression_algo: CompressionType::LZ4,
             bloom_bits_per_key: BLOOM_BITS_PER_KEY,
+            max_key_size: DEFAULT_MAX_KEY_SIZE,
+            max_value_size: DEFAULT_MAX_VALUE_SIZE,
+            max_immutable_memtables: DEFAULT_MAX_IMMUTABLE_MEMTABLES,
         }
     }
 }
 
 impl TaurusConfig {
+    pub fn mem_table_size(&self) -> u64 {
+        self.mem_table_size
+    }
+
+    pub fn max_key_size(&self) -> u64 {
+        self.max_key_size
+    }
+
+    pub fn max_value_size(&self) -> u64 {
+        self.max_value_size
+    }
+
+    pub fn max_immutable_memtables(&self) -> usize {
+        self.max_immutable_memtables
+    }
+
     pub fn validate(&self) -> Result<(), TaurusConfigErrors> {
         let mut err = TaurusConfigErrors::new();
 
@@ -80,9 +106,96 @@ impl TaurusConfig {
             ));
         }
 
+        // The WAL length prefixes are u32 and the encoded key carries 9 extra bytes of metadata
+        if self.max_key_size == 0 || self.max_key_size > u32::MAX as u64 - 9 {
+            err.errors
+                .push(TaurusConfigError::MaxKeySizeOutOfRange(self.max_key_size));
+        }
+
+        if self.max_value_size > u32::MAX as u64 {
+            err.errors
+                .push(TaurusConfigError::MaxValueSizeTooLarge(self.max_value_size));
+        }
+
+        if !(1..=16).contains(&self.max_immutable_memtables) {
+            err.errors
+                .push(TaurusConfigError::MaxImmutableMemtablesOutOfRange(
+                    self.max_immutable_memtables,
+                ));
+        }
+
         if err.errors.is_empty() {
             return Ok(());
         }
         Err(err)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_is_valid() {
+        assert!(TaurusConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    fn test_max_immutable_memtables_bounds() {
+        let mut config = TaurusConfig {
+            max_immutable_memtables: 0,
+            ..TaurusConfig::default()
+        };
+        let err = config.validate().unwrap_err();
+        assert!(matches!(
+            err.errors[..],
+            [TaurusConfigError::MaxImmutableMemtablesOutOfRange(0)]
+        ));
+
+        config.max_immutable_memtables = 17;
+        assert!(config.validate().is_err());
+
+        config.max_immutable_memtables = 1;
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_max_key_size_bounds() {
+        let mut config = TaurusConfig {
+            max_key_size: 0,
+            ..TaurusConfig::default()
+        };
+        let err = config.validate().unwrap_err();
+        assert!(matches!(
+            err.errors[..],
+            [TaurusConfigError::MaxKeySizeOutOfRange(0)]
+        ));
+
+        // Largest key whose encoded form (plus 9 bytes of metadata) still fits a u32 length
+        config.max_key_size = u32::MAX as u64 - 9;
+        assert!(config.validate().is_ok());
+
+        config.max_key_size = u32::MAX as u64 - 8;
+        let err = config.validate().unwrap_err();
+        assert!(matches!(
+            err.errors[..],
+            [TaurusConfigError::MaxKeySizeOutOfRange(size)] if size == u32::MAX as u64 - 8
+        ));
+    }
+
+    #[test]
+    fn test_max_value_size_bounds() {
+        let mut config = TaurusConfig {
+            max_value_size: u32::MAX as u64,
+            ..TaurusConfig::default()
+        };
+        assert!(config.validate().is_ok());
+
+        config.max_value_size = u32::MAX as u64 + 1;
+        let err = config.validate().unwrap_err();
+        assert!(matches!(
+            err.errors[..],
+            [TaurusConfigError::MaxValueSizeTooLarge(size)] if size == u32::MAX as u64 + 1
+        ));
+    }
+}
@@ -8,6 +8,7 @@ pub enum CompactionConfigError {
     L0NotEnoughFiles(u8),
     TargetFileSizeTooLow(u64),
     MaxBytesTargetSizeMismatch,
+    TargetFileSizeMultiplierZero,
 }
 
 impl Error for CompactionConfigError {}
@@ -56,6 +57,12 @@ impl Display for CompactionConfigError {
                     "Compaction Config Err: max_bytes_for_level_base should be a multiple of or larger than target_file_size_base"
                 )
             }
+            CompactionConfigError::TargetFileSizeMultiplierZero => {
+                write!(
+                    f,
+                    "Compaction Config Err: target file size multiplier must be >= 1"
+                )
+            }
         }
     }
 }
@@ -173,6 +180,7 @@ pub enum PerformanceConfigError {
     WalBatchBytesZero,
     WalPeriodicIntervalZero,
     ScanParallelismExceedsReadThreads(usize, usize),
+    WalPreallocateSizeTooHigh(usize),
 }
 
 impl Error for PerformanceConfigError {}
@@ -219,6 +227,13 @@ impl Display for PerformanceConfigError {
                     scan, read
                 )
             }
+            PerformanceConfigError::WalPreallocateSizeTooHigh(size) => {
+                write!(
+                    f,
+                    "Performance Config Err: WAL preallocate size must be <= 1 GB (found {})",
+                    size
+                )
+            }
         }
     }
 }
@@ -263,6 +278,9 @@ pub enum TaurusConfigError {
     MemtableSmallerThanBlock(u64, u64),
     BloomBitsPerKeyTooLow(u8),
     BloomBitsPerKeyTooHigh(u8),
+    MaxKeySizeOutOfRange(u64),
+    MaxValueSizeTooLarge(u64),
+    MaxImmutableMemtablesOutOfRange(usize),
 }
 
 impl Error for TaurusConfigError {}
@@ -326,6 +344,27 @@ impl Display for TaurusConfigError {
                     bits
                 )
             }
+            TaurusConfigError::MaxKeySizeOutOfRange(size) => {
+                write!(
+                    f,
+                    "Taurus Config Err: max key size must be > 0 and <= u32::MAX - 9 (found {})",
+                    size
+                )
+            }
+            TaurusConfigError::MaxValueSizeTooLarge(size) => {
+                write!(
+                    f,
+                    "Taurus Config Err: max value size must be <= u32::MAX (found {})",
+                    size
+                )
+            }
+            TaurusConfigError::MaxImmutableMemtablesOutOfRange(count) => {
+                write!(
+                    f,
+                    "Taurus Config Err: max immutable memtables must be between 1 and 16 (found {})",
+                    count
+                )
+            }
         }
     }
 }
@@ -406,3 +445,44 @@ impl Error for CacheConfigErrors {
         self.errors.first().map(|e| e as &(dyn Error + 'static))
     }
 }
+
+// ===========================================
+// |        Cross Config Errors              |
+// ===========================================
+
+#[derive(Debug)]
+pub enum CrossConfigError {
+    WalSizeMemtableMismatch(usize, u64),
+    ReadaheadExceedsBlockCache(usize, u64),
+    WalReplayLimitBelowWalSize(usize, usize),
+}
+
+impl Error for CrossConfigError {}
+
+impl Display for CrossConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CrossConfigError::WalSizeMemtableMismatch(wal, memtable) => {
+                write!(
+                    f,
+                    "Cross Config Err: wal max size ({}) should be within 8x of memtable size ({})",
+                    wal, memtable
+                )
+            }
+            CrossConfigError::ReadaheadExceedsBlockCache(readahead, cache) => {
+                write!(
+                    f,
+                    "Cross Config Err: readahead size ({}) exceeds block cache size ({})",
+                    readahead, cache
+                )
+            }
+            CrossConfigError::WalReplayLimitBelowWalSize(limit, wal) => {
+                write!(
+                    f,
+                    "Cross Config Err: wal max replay size ({}) is below wal max size ({}), a full log could not be recovered",
+                    limit, wal
+                )
+            }
+        }
+    }
+}
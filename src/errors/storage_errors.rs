@@ -3,6 +3,26 @@ use std::{error::Error, fmt::Display};
 #[derive(Debug)]
 pub enum StorageError {
     DecodeError(String),
+    KeyTooLarge(usize, usize),
+    ValueTooLarge(usize, usize),
+    LengthOverflow(usize),
+    TruncatedRecord(u64),
+    ChecksumMismatch(u64),
+    SequenceExhausted(u64),
+    // (records replayed, bytes replayed, limit)
+    ReplayLimitExceeded(usize, u64, u64),
+}
+
+impl StorageError {
+    // Decoders report positions relative to the slice they were given, callers that know where
+    // that slice starts in the file use this to make the offset absolute
+    pub fn at_offset(self, base: u64) -> Self {
+        match self {
+            StorageError::TruncatedRecord(offset) => StorageError::TruncatedRecord(base + offset),
+            StorageError::ChecksumMismatch(offset) => StorageError::ChecksumMismatch(base + offset),
+            other => other,
+        }
+    }
 }
 
 impl Error for StorageError {}
@@ -11,6 +31,51 @@ impl Display for StorageError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             StorageError::DecodeError(err) => write!(f, "Decode Error: {}", err),
+            StorageError::KeyTooLarge(size, max) => {
+                write!(f, "Key Too Large: key is {} bytes, max is {}", size, max)
+            }
+            StorageError::ValueTooLarge(size, max) => {
+                write!(
+                    f,
+                    "Value Too Large: value is {} bytes, max is {}",
+                    size, max
+                )
+            }
+            StorageError::TruncatedRecord(offset) => {
+                write!(
+                    f,
+                    "Truncated Record: record at offset {} is incomplete",
+                    offset
+                )
+            }
+            StorageError::ChecksumMismatch(offset) => {
+                write!(
+                    f,
+                    "Checksum Mismatch: record at offset {} is corrupt",
+                    offset
+                )
+            }
+            StorageError::SequenceExhausted(last) => {
+                write!(
+                    f,
+                    "Sequence Exhausted: last sequence number {} was the maximum",
+                    last
+                )
+            }
+            StorageError::ReplayLimitExceeded(records, bytes, limit) => {
+                write!(
+                    f,
+                    "Replay Limit Exceeded: replayed {} records ({} bytes) before reaching the {} byte limit, raise the limit or force the replay",
+                    records, bytes, limit
+                )
+            }
+            StorageError::LengthOverflow(len) => {
+                write!(
+                    f,
+                    "Length Overflow: {} does not fit in a u32 length prefix",
+                    len
+                )
+            }
         }
     }
 }
@@ -1,18 +1,43 @@
 use std::{
     fmt::Display,
-    fs::{OpenOptions, create_dir_all, read_dir, rename},
-    io::{Error, ErrorKind, Read, Write},
+    io::{Error, ErrorKind, Write},
     path::{Path, PathBuf},
-    sync::atomic::AtomicU64,
+    sync::{Arc, Mutex, atomic::AtomicU64},
 };
 
+use crate::{
+    errors::storage_errors::StorageError,
+    storage::sequence::SequenceAllocator,
+    vfs::{FileSystem, StdFs, WritableFile},
+};
+
+// Version 0 is the original manifest, which had no format_version line
+pub const MANIFEST_FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum LockMode {
+    // The LOCK file's existence is the lock. A crashed process leaves it behind.
+    #[default]
+    CreateNew,
+    // An OS advisory lock on the LOCK file, released automatically when the process exits. Every
+    // process opening the db must use the same mode.
+    Advisory,
+}
+
 #[derive(Debug)]
 pub struct FileManager {
     db_dir_path: PathBuf,
     next_file_number: AtomicU64,
+    sequence: SequenceAllocator,
+    lock_released: bool,
+    batch_dir_syncs: bool,
+    pending_dir_entries: Mutex<Vec<PathBuf>>,
+    fs: Arc<dyn FileSystem>,
+    // Held for the lifetime of the manager in LockMode::Advisory
+    lock_file: Option<Mutex<Box<dyn WritableFile>>>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Name {
     SSTable,
     WriteAheadLog,
@@ -35,39 +60,77 @@ impl Display for Name {
 
 impl FileManager {
     pub fn new(path: PathBuf) -> Result<Self, Error> {
-        if path.exists() && path.is_file() {
+        Self::new_with_fs(path, Arc::new(StdFs))
+    }
+
+    pub fn new_with_fs(path: PathBuf, fs: Arc<dyn FileSystem>) -> Result<Self, Error> {
+        Self::new_with_lock_mode(path, fs, LockMode::CreateNew)
+    }
+
+    pub fn new_with_lock_mode(
+        path: PathBuf,
+        fs: Arc<dyn FileSystem>,
+        lock_mode: LockMode,
+    ) -> Result<Self, Error> {
+        if fs.exists(&path) && fs.is_file(&path) {
             return Err(Error::new(
                 ErrorKind::AlreadyExists,
                 "db already exists as a file at that location",
             ));
-        } else if path.exists() && path.is_dir() {
-            let mut contents = read_dir(&path)?;
-            if contents.next().is_some() {
+        } else if fs.exists(&path) && fs.is_dir(&path) {
+            let contents = fs.read_dir(&path)?;
+            if !contents.is_empty() {
                 return Err(Error::new(
                     ErrorKind::AlreadyExists,
                     "db already exists at that location",
                 ));
             }
         } else {
-            create_dir_all(&path)?;
+            fs.create_dir_all(&path)?;
         }
 
-        initialize_db_files(&path)?;
+        let lp = path.join("LOCK");
+        let lock_file = acquire_lock(fs.as_ref(), &lp, lock_mode)?;
+
+        if let Err(e) = initialize_db_files(fs.as_ref(), &path) {
+            if lock_file.is_none() {
+                let _ = fs.remove_file(&lp);
+            }
+            return Err(e);
+        }
 
         Ok(FileManager {
             db_dir_path: path,
             next_file_number: AtomicU64::new(2),
+            sequence: SequenceAllocator::new(0),
+            lock_released: false,
+            batch_dir_syncs: false,
+            pending_dir_entries: Mutex::new(Vec::new()),
+            fs,
+            lock_file: lock_file.map(Mutex::new),
         })
     }
 
     pub fn open_existing(path: PathBuf) -> Result<Self, Error> {
-        if !path.exists() {
+        Self::open_existing_with_fs(path, Arc::new(StdFs))
+    }
+
+    pub fn open_existing_with_fs(path: PathBuf, fs: Arc<dyn FileSystem>) -> Result<Self, Error> {
+        Self::open_existing_with_lock_mode(path, fs, LockMode::CreateNew)
+    }
+
+    pub fn open_existing_with_lock_mode(
+        path: PathBuf,
+        fs: Arc<dyn FileSystem>,
+        lock_mode: LockMode,
+    ) -> Result<Self, Error> {
+        if !fs.exists(&path) {
             return Err(Error::new(ErrorKind::NotFound, "db directory not found"));
         }
 
         let cp = path.join("CURRENT");
 
-        if !cp.exists() {
+        if !fs.exists(&cp) {
             return Err(Error::new(
                 ErrorKind::NotFound,
                 "path exists, but db not initialized within",
@@ -75,47 +138,99 @@ impl FileManager {
         }
 
         let lp = path.join("LOCK");
-        match OpenOptions::new().write(true).create_new(true).open(&lp) {
-            Ok(mut file) => {
-                file.write_all(std::process::id().to_string().as_bytes())?;
-                file.sync_all()?;
-            }
-            Err(e) => {
-                if e.kind() == ErrorKind::AlreadyExists {
-                    let mut lf = OpenOptions::new().read(true).open(&lp)?;
-                    let mut pid: String = String::new();
-                    lf.read_to_string(&mut pid)?;
-                    return Err(Error::new(
-                        ErrorKind::AlreadyExists,
-                        format!("database already open by process {pid}"),
-                    ));
-                } else {
-                    return Err(e);
-                }
-            }
-        }
+        let lock_file = acquire_lock(fs.as_ref(), &lp, lock_mode)?;
 
-        let mut cf = OpenOptions::new().read(true).open(&cp)?;
+        let manifest = read_current_manifest(fs.as_ref(), &path).and_then(|manifest_path| {
+            let contents = fs.read_to_string(&manifest_path)?;
+            let version = get_format_version(&contents)?;
+
+            // A newer format may lay out the other fields differently, so reject it before parsing them
+            if version > MANIFEST_FORMAT_VERSION {
+                return Err(Error::new(
+                    ErrorKind::Unsupported,
+                    format!(
+                        "manifest format version {version} is newer than the supported version {MANIFEST_FORMAT_VERSION}"
+                    ),
+                ));
+            }
 
-        let mut contents = String::new();
-        cf.read_to_string(&mut contents)?;
+            let next_file = get_next_file_num(&contents)?;
+            let last_sequence = get_last_sequence(&contents)?;
+            if version < MANIFEST_FORMAT_VERSION {
+                write_manifest(
+                    fs.as_ref(),
+                    &manifest_path,
+                    next_file.load(std::sync::atomic::Ordering::SeqCst),
+                    last_sequence,
+                )?;
+            }
 
-        let manifest_name = Path::new(contents.trim());
-        let manifest_path = path.join(manifest_name);
+            Ok((next_file, last_sequence))
+        });
 
-        let next_file = get_next_file_num(&manifest_path)?;
+        let (next_file, last_sequence) = match manifest {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                // Don't leave a stale LOCK behind when the db can't be opened
+                if lock_file.is_none() {
+                    let _ = fs.remove_file(&lp);
+                }
+                return Err(e);
+            }
+        };
 
         Ok(FileManager {
             db_dir_path: path,
             next_file_number: next_file,
+            sequence: SequenceAllocator::new(last_sequence),
+            lock_released: false,
+            batch_dir_syncs: false,
+            pending_dir_entries: Mutex::new(Vec::new()),
+            fs,
+            lock_file: lock_file.map(Mutex::new),
         })
     }
 
+    pub fn open_or_create(path: PathBuf) -> Result<Self, Error> {
+        Self::open_or_create_with_fs(path, Arc::new(StdFs))
+    }
+
+    pub fn open_or_create_with_fs(path: PathBuf, fs: Arc<dyn FileSystem>) -> Result<Self, Error> {
+        Self::open_or_create_with_lock_mode(path, fs, LockMode::CreateNew)
+    }
+
+    pub fn open_or_create_with_lock_mode(
+        path: PathBuf,
+        fs: Arc<dyn FileSystem>,
+        lock_mode: LockMode,
+    ) -> Result<Self, Error> {
+        if fs.is_dir(&path) && fs.exists(&path.join("CURRENT")) {
+            return Self::open_existing_with_lock_mode(path, fs, lock_mode);
+        }
+
+        if fs.is_dir(&path) && !fs.read_dir(&path)?.is_empty() {
+            return Err(Error::new(
+                ErrorKind::AlreadyExists,
+                "directory is not empty and does not contain a taurusdb database",
+            ));
+        }
+
+        Self::new_with_lock_mode(path, fs, lock_mode)
+    }
+
     pub fn new_file_number(&self) -> u64 {
         self.next_file_number
             .fetch_add(1, std::sync::atomic::Ordering::SeqCst)
     }
 
+    pub fn next_sequence(&self) -> Result<u64, StorageError> {
+        self.sequence.next()
+    }
+
+    pub fn last_sequence(&self) -> u64 {
+        self.sequence.last_sequence()
+    }
+
     pub fn generate_filename(&self, file_type: Name, number: Option<u64>) -> PathBuf {
         let path = match file_type {
             Name::SSTable => {
@@ -147,61 +262,222 @@ impl FileManager {
 
         self.db_dir_path.join(path)
     }
+
+    // When enabled, created/deleted files are only recorded and the directory is fsynced once by
+    // sync_dir_pending, which must run before any VersionEdit referencing those files is committed
+    pub fn set_batch_dir_syncs(&mut self, enabled: bool) {
+        self.batch_dir_syncs = enabled;
+    }
+
+    pub fn record_dir_change(&self, path: PathBuf) -> Result<(), Error> {
+        if !self.batch_dir_syncs {
+            return self.fs.sync_dir(&self.db_dir_path);
+        }
+
+        self.pending_dir_entries
+            .lock()
+            .map_err(|_| Error::other("pending dir entries lock poisoned"))?
+            .push(path);
+        Ok(())
+    }
+
+    pub fn sync_dir_pending(&self) -> Result<usize, Error> {
+        let mut pending = self
+            .pending_dir_entries
+            .lock()
+            .map_err(|_| Error::other("pending dir entries lock poisoned"))?;
+
+        if pending.is_empty() {
+            return Ok(0);
+        }
+
+        self.fs.sync_dir(&self.db_dir_path)?;
+
+        let synced = pending.len();
+        pending.clear();
+        Ok(synced)
+    }
+
+    // Records the current file number and last sequence so a reopen never hands either out again.
+    // Pending directory entries are synced first, since the manifest may reference those files.
+    pub fn persist_manifest(&self) -> Result<(), Error> {
+        self.sync_dir_pending()?;
+        let manifest_path = read_current_manifest(self.fs.as_ref(), &self.db_dir_path)?;
+        write_manifest(
+            self.fs.as_ref(),
+            &manifest_path,
+            self.next_file_number
+                .load(std::sync::atomic::Ordering::SeqCst),
+            self.last_sequence(),
+        )
+    }
+
+    // Persists the manifest and releases the LOCK, reporting any failure. Drop only releases the
+    // LOCK, on a best-effort basis.
+    pub fn close(mut self) -> Result<(), Error> {
+        self.lock_released = true;
+        let persisted = self.persist_manifest();
+
+        // The advisory lock is released when the file is dropped. The LOCK file stays, since
+        // removing it could race with another process that is locking it.
+        let released = if self.lock_file.take().is_some() {
+            Ok(())
+        } else {
+            let lock_path = self.db_dir_path.join("LOCK");
+            self.fs.remove_file(&lock_path)
+        };
+
+        persisted.and(released)
+    }
 }
 
 impl Drop for FileManager {
     fn drop(&mut self) {
+        if self.lock_released || self.lock_file.is_some() {
+            return;
+        }
         let lock_path = self.db_dir_path.join("LOCK");
-        let _ = std::fs::remove_file(lock_path);
+        let _ = self.fs.remove_file(&lock_path);
     }
 }
 
-fn initialize_db_files(path: &PathBuf) -> Result<(), Error> {
-    let lock_path = path.join("LOCK");
-    let mut lf = OpenOptions::new()
-        .write(true)
-        .create_new(true)
-        .open(lock_path)?;
+// Returns the held file in LockMode::Advisory. Both modes write the pid for diagnostics.
+fn acquire_lock(
+    fs: &dyn FileSystem,
+    lock_path: &Path,
+    lock_mode: LockMode,
+) -> Result<Option<Box<dyn WritableFile>>, Error> {
+    let locked = match lock_mode {
+        LockMode::CreateNew => fs.create_new(lock_path),
+        LockMode::Advisory => fs.lock_file(lock_path).map_err(|e| {
+            if e.kind() == ErrorKind::WouldBlock {
+                Error::new(ErrorKind::AlreadyExists, e)
+            } else {
+                e
+            }
+        }),
+    };
+
+    let mut file = match locked {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+            // Windows locks are mandatory, so the holder's pid can't be read while it is held
+            let message = match (fs.read_to_string(lock_path), lock_mode) {
+                (Ok(pid), _) => format!("database already open by process {pid}"),
+                (Err(_), LockMode::Advisory) => "database already open".to_string(),
+                (Err(e), LockMode::CreateNew) => return Err(e),
+            };
+            return Err(Error::new(ErrorKind::AlreadyExists, message));
+        }
+        Err(e) => return Err(e),
+    };
+
+    file.set_len(0)?;
+    file.write_all(std::process::id().to_string().as_bytes())?;
+    file.sync()?;
 
-    lf.write_all(std::process::id().to_string().as_bytes())?;
-    lf.sync_all()?;
+    match lock_mode {
+        LockMode::CreateNew => Ok(None),
+        LockMode::Advisory => Ok(Some(file)),
+    }
+}
 
+fn initialize_db_files(fs: &dyn FileSystem, path: &Path) -> Result<(), Error> {
     let manifest_path = path.join("MANIFEST-000001");
-    let mut mf = OpenOptions::new()
-        .write(true)
-        .create_new(true)
-        .open(manifest_path)?;
+    let mut mf = fs.create_new(&manifest_path)?;
 
-    mf.write_all(b"next_file_number: 2\n")?;
-    mf.sync_all()?;
+    mf.write_all(manifest_contents(2, 0).as_bytes())?;
+    mf.sync()?;
 
     let curtmp_path = path.join("CURRENT.tmp");
     let current_path = path.join("CURRENT");
-    let mut cf = OpenOptions::new()
-        .write(true)
-        .create(true)
-        .truncate(true)
-        .open(&curtmp_path)?;
+    let mut cf = fs.create(&curtmp_path)?;
 
     cf.write_all(b"MANIFEST-000001\n")?;
-    cf.sync_all()?;
+    cf.sync()?;
     drop(cf);
 
-    rename(&curtmp_path, &current_path)?;
+    fs.rename(&curtmp_path, &current_path)?;
+    fs.sync_dir(path)?;
+
+    Ok(())
+}
+
+// Inverse of generate_filename. Numbers are zero-padded to at least six digits and simply grow
+// wider past 999999, so any run of six or more digits that fits in a u64 is accepted.
+pub fn parse_filename(file_name: &str) -> Option<(Name, Option<u64>)> {
+    let parse_number = |digits: &str| -> Option<u64> {
+        if digits.len() < 6 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        digits.parse::<u64>().ok()
+    };
+
+    match file_name {
+        "CURRENT" => Some((Name::Current, None)),
+        "LOCK" => Some((Name::Lock, None)),
+        _ => {
+            if let Some(digits) = file_name.strip_prefix("MANIFEST-") {
+                return Some((Name::Manifest, Some(parse_number(digits)?)));
+            }
+            if let Some(digits) = file_name.strip_suffix(".sst") {
+                return Some((Name::SSTable, Some(parse_number(digits)?)));
+            }
+            if let Some(digits) = file_name.strip_suffix(".log") {
+                return Some((Name::WriteAheadLog, Some(parse_number(digits)?)));
+            }
+            None
+        }
+    }
+}
+
+fn manifest_contents(next_file_number: u64, last_sequence: u64) -> String {
+    format!(
+        "format_version: {MANIFEST_FORMAT_VERSION}\nnext_file_number: {next_file_number}\nlast_sequence: {last_sequence}\n"
+    )
+}
+
+// Rewrites the manifest in the current format. The new contents go to a temp file that is renamed
+// over the original, so a crash leaves either the old or the new manifest intact.
+fn write_manifest(
+    fs: &dyn FileSystem,
+    manifest_path: &Path,
+    next_file_number: u64,
+    last_sequence: u64,
+) -> Result<(), Error> {
+    let mut tmp_name = manifest_path.as_os_str().to_owned();
+    tmp_name.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_name);
+
+    let mut file = fs.create(&tmp_path)?;
+    file.write_all(manifest_contents(next_file_number, last_sequence).as_bytes())?;
+    file.sync()?;
+    drop(file);
+
+    fs.rename(&tmp_path, manifest_path)?;
+    if let Some(parent) = manifest_path.parent() {
+        fs.sync_dir(parent)?;
+    }
 
     Ok(())
 }
 
-fn get_next_file_num(manifest_path: &Path) -> Result<AtomicU64, Error> {
-    let mut mf = OpenOptions::new()
-        .read(true)
-        .create(false)
-        .open(manifest_path)?;
+fn read_current_manifest(fs: &dyn FileSystem, path: &Path) -> Result<PathBuf, Error> {
+    let contents = fs.read_to_string(&path.join("CURRENT"))?;
 
-    let mut manifest_contents = String::new();
+    // A torn write can leave CURRENT blank, which would otherwise join to the db dir itself
+    let manifest_name = contents.trim();
+    if manifest_name.is_empty() {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "CURRENT file is empty or corrupt",
+        ));
+    }
 
-    mf.read_to_string(&mut manifest_contents)?;
+    Ok(path.join(manifest_name))
+}
 
+fn get_next_file_num(manifest_contents: &str) -> Result<AtomicU64, Error> {
     let line = manifest_contents
         .lines()
         .find_map(|line| {
@@ -218,11 +494,39 @@ fn get_next_file_num(manifest_path: &Path) -> Result<AtomicU64, Error> {
     Ok(AtomicU64::from(line))
 }
 
+fn get_format_version(manifest_contents: &str) -> Result<u32, Error> {
+    match manifest_contents
+        .lines()
+        .find_map(|line| line.strip_prefix("format_version:"))
+    {
+        Some(value) => value
+            .trim()
+            .parse::<u32>()
+            .map_err(|_| Error::new(ErrorKind::InvalidData, "format_version is invalid")),
+        None => Ok(0),
+    }
+}
+
+// Manifests written before sequence numbers were persisted have no last_sequence line
+fn get_last_sequence(manifest_contents: &str) -> Result<u64, Error> {
+    match manifest_contents
+        .lines()
+        .find_map(|line| line.strip_prefix("last_sequence:"))
+    {
+        Some(value) => value
+            .trim()
+            .parse::<u64>()
+            .map_err(|_| Error::new(ErrorKind::InvalidData, "last_sequence is invalid")),
+        None => Ok(0),
+    }
+}
+
 // I was learning this as I build it, I generated the tests using GPT 5.1 + Gemini 3 because I
 // didn't trust myself to not implement tests in a way that covered what I needed
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::vfs::InMemoryFs;
     use std::fs;
     use std::sync::Arc;
     use std::thread;
@@ -234,27 +538,96 @@ mod tests {
         TempDir::new().expect("Failed to create temp directory")
     }
 
-    #[test]
-    fn test_create_new_database() {
+    fn setup_std_fs() -> (Arc<dyn FileSystem>, PathBuf, Option<TempDir>) {
         let temp_dir = setup_temp_dir();
-        let db_path = temp_dir.path().to_path_buf();
+        let db_path = temp_dir.path().join("db");
+        (Arc::new(StdFs), db_path, Some(temp_dir))
+    }
 
-        let fm = FileManager::new(db_path.clone()).expect("Failed to create database");
+    fn setup_in_memory() -> (Arc<dyn FileSystem>, PathBuf, Option<TempDir>) {
+        (
+            Arc::new(InMemoryFs::new()),
+            PathBuf::from("/taurus/db"),
+            None,
+        )
+    }
+
+    // Runs each test below once per FileSystem backend. The TempDir, if any, lives until the
+    // test returns.
+    macro_rules! backend_tests {
+        ($($name:ident),* $(,)?) => {
+            mod std_fs {
+                $(
+                    #[test]
+                    fn $name() {
+                        let (fs, db_path, _temp_dir) = super::setup_std_fs();
+                        super::$name(fs, db_path);
+                    }
+                )*
+            }
+
+            mod in_memory {
+                $(
+                    #[test]
+                    fn $name() {
+                        let (fs, db_path, _temp_dir) = super::setup_in_memory();
+                        super::$name(fs, db_path);
+                    }
+                )*
+            }
+        };
+    }
+
+    backend_tests!(
+        test_create_new_database,
+        test_create_fails_on_file,
+        test_create_fails_on_non_empty_directory,
+        test_open_existing_database,
+        test_open_fails_on_nonexistent_directory,
+        test_open_fails_without_current_file,
+        test_lock_prevents_concurrent_open,
+        test_close_releases_lock_and_allows_reopen,
+        test_close_persists_last_sequence,
+        test_persist_manifest_syncs_pending_dir_entries,
+        test_open_fails_on_empty_current_file,
+        test_last_sequence_recovered_from_manifest,
+        test_open_upgrades_unversioned_manifest,
+        test_open_rejects_newer_manifest_format,
+        test_open_rejects_newer_manifest_before_parsing_fields,
+        test_open_or_create_creates_missing_database,
+        test_open_or_create_opens_existing_database,
+        test_open_or_create_rejects_foreign_directory,
+        test_lock_cleanup_on_drop,
+        test_persistence_across_restarts,
+        test_atomic_current_file_creation,
+        test_batched_dir_sync,
+        test_unbatched_dir_sync_is_immediate,
+        test_close_surfaces_lock_removal_error,
+    );
+
+    fn test_create_new_database(fs: Arc<dyn FileSystem>, db_path: PathBuf) {
+        let fm = FileManager::new_with_fs(db_path.clone(), fs.clone())
+            .expect("Failed to create database");
 
         // Verify files exist
-        assert!(db_path.join("LOCK").exists(), "LOCK file should exist");
+        assert!(fs.is_file(&db_path.join("LOCK")), "LOCK file should exist");
         assert!(
-            db_path.join("MANIFEST-000001").exists(),
+            fs.is_file(&db_path.join("MANIFEST-000001")),
             "MANIFEST file should exist"
         );
         assert!(
-            db_path.join("CURRENT").exists(),
+            fs.is_file(&db_path.join("CURRENT")),
             "CURRENT file should exist"
         );
+        assert!(
+            !fs.exists(&db_path.join("CURRENT.tmp")),
+            "CURRENT.tmp should be cleaned up after atomic rename"
+        );
 
         // Verify CURRENT content points to manifest
-        let current_content =
-            fs::read_to_string(db_path.join("CURRENT")).expect("Failed to read CURRENT");
+        let current_content = fs
+            .read_to_string(&db_path.join("CURRENT"))
+            .expect("Failed to read CURRENT");
         assert_eq!(
             current_content.trim(),
             "MANIFEST-000001",
@@ -262,11 +635,12 @@ mod tests {
         );
 
         // Verify MANIFEST content
-        let manifest_content =
-            fs::read_to_string(db_path.join("MANIFEST-000001")).expect("Failed to read MANIFEST");
+        let manifest_content = fs
+            .read_to_string(&db_path.join("MANIFEST-000001"))
+            .expect("Failed to read MANIFEST");
         assert_eq!(
-            manifest_content, "next_file_number: 2\n",
-            "MANIFEST should contain next_file_number: 2"
+            manifest_content, "format_version: 1\nnext_file_number: 2\nlast_sequence: 0\n",
+            "MANIFEST should contain next_file_number: 2 and last_sequence: 0"
         );
 
         // Verify first file number is 2
@@ -274,16 +648,14 @@ mod tests {
         assert_eq!(file_num, 2, "First file number should be 2");
     }
 
-    #[test]
-    fn test_create_fails_on_file() {
-        let temp_dir = setup_temp_dir();
-        let file_path = temp_dir.path().join("testfile");
-
+    fn test_create_fails_on_file(fs: Arc<dyn FileSystem>, db_path: PathBuf) {
         // Create a regular file
-        fs::write(&file_path, "test").expect("Failed to create file");
+        fs.create_dir_all(db_path.parent().unwrap())
+            .expect("Failed to create parent");
+        fs.create_new(&db_path).expect("Failed to create file");
 
         // Try to create database at file location
-        let result = FileManager::new(file_path);
+        let result = FileManager::new_with_fs(db_path, fs);
 
         assert!(result.is_err(), "Should fail when path is a file");
         assert_eq!(
@@ -293,16 +665,15 @@ mod tests {
         );
     }
 
-    #[test]
-    fn test_create_fails_on_non_empty_directory() {
-        let temp_dir = setup_temp_dir();
-        let db_path = temp_dir.path().to_path_buf();
-
+    fn test_create_fails_on_non_empty_directory(fs: Arc<dyn FileSystem>, db_path: PathBuf) {
         // Create a file in the directory
-        fs::write(db_path.join("somefile.txt"), "content").expect("Failed to create file");
+        fs.create_dir_all(&db_path)
+            .expect("Failed to create directory");
+        fs.create_new(&db_path.join("somefile.txt"))
+            .expect("Failed to create file");
 
         // Try to create database in non-empty directory
-        let result = FileManager::new(db_path);
+        let result = FileManager::new_with_fs(db_path, fs);
 
         assert!(result.is_err(), "Should fail when directory is not empty");
         assert_eq!(
@@ -312,29 +683,27 @@ mod tests {
         );
     }
 
-    #[test]
-    fn test_open_existing_database() {
-        let temp_dir = setup_temp_dir();
-        let db_path = temp_dir.path().to_path_buf();
-
+    fn test_open_existing_database(fs: Arc<dyn FileSystem>, db_path: PathBuf) {
         // Create database
         {
-            let _fm = FileManager::new(db_path.clone()).expect("Failed to create database");
+            let _fm = FileManager::new_with_fs(db_path.clone(), fs.clone())
+                .expect("Failed to create database");
             // fm dropped here, LOCK released
         }
 
         // Verify LOCK was cleaned up
         assert!(
-            !db_path.join("LOCK").exists(),
+            !fs.exists(&db_path.join("LOCK")),
             "LOCK should be removed on drop"
         );
 
         // Reopen database
-        let fm = FileManager::open_existing(db_path.clone()).expect("Failed to open database");
+        let fm = FileManager::open_existing_with_fs(db_path.clone(), fs.clone())
+            .expect("Failed to open database");
 
         // Verify LOCK was reacquired
         assert!(
-            db_path.join("LOCK").exists(),
+            fs.exists(&db_path.join("LOCK")),
             "LOCK should exist after reopening"
         );
 
@@ -343,12 +712,8 @@ mod tests {
         assert_eq!(file_num, 2, "First file number after reopen should be 2");
     }
 
-    #[test]
-    fn test_open_fails_on_nonexistent_directory() {
-        let temp_dir = setup_temp_dir();
-        let nonexistent_path = temp_dir.path().join("nonexistent");
-
-        let result = FileManager::open_existing(nonexistent_path);
+    fn test_open_fails_on_nonexistent_directory(fs: Arc<dyn FileSystem>, db_path: PathBuf) {
+        let result = FileManager::open_existing_with_fs(db_path, fs);
 
         assert!(result.is_err(), "Should fail on nonexistent directory");
         assert_eq!(
@@ -358,15 +723,12 @@ mod tests {
         );
     }
 
-    #[test]
-    fn test_open_fails_without_current_file() {
-        let temp_dir = setup_temp_dir();
-        let db_path = temp_dir.path().to_path_buf();
-
+    fn test_open_fails_without_current_file(fs: Arc<dyn FileSystem>, db_path: PathBuf) {
         // Create directory but no database files
-        fs::create_dir_all(&db_path).expect("Failed to create directory");
+        fs.create_dir_all(&db_path)
+            .expect("Failed to create directory");
 
-        let result = FileManager::open_existing(db_path);
+        let result = FileManager::open_existing_with_fs(db_path, fs);
 
         assert!(
             result.is_err(),
@@ -379,16 +741,13 @@ mod tests {
         );
     }
 
-    #[test]
-    fn test_lock_prevents_concurrent_open() {
-        let temp_dir = setup_temp_dir();
-        let db_path = temp_dir.path().to_path_buf();
-
+    fn test_lock_prevents_concurrent_open(fs: Arc<dyn FileSystem>, db_path: PathBuf) {
         // Create database and keep it open
-        let _fm1 = FileManager::new(db_path.clone()).expect("Failed to create database");
+        let _fm1 = FileManager::new_with_fs(db_path.clone(), fs.clone())
+            .expect("Failed to create database");
 
         // Try to open again while first is still open
-        let result = FileManager::open_existing(db_path);
+        let result = FileManager::open_existing_with_fs(db_path, fs);
 
         assert!(result.is_err(), "Should fail when database is locked");
         assert_eq!(
@@ -398,6 +757,447 @@ mod tests {
         );
     }
 
+    fn test_close_releases_lock_and_allows_reopen(fs: Arc<dyn FileSystem>, db_path: PathBuf) {
+        let fm = FileManager::new_with_fs(db_path.clone(), fs.clone())
+            .expect("Failed to create database");
+        fm.close().expect("Close should succeed");
+
+        assert!(
+            !fs.exists(&db_path.join("LOCK")),
+            "LOCK should be removed after close"
+        );
+
+        let fm = FileManager::open_existing_with_fs(db_path.clone(), fs)
+            .expect("Failed to reopen database");
+        assert_eq!(fm.new_file_number(), 2);
+        fm.close().expect("Close should succeed");
+    }
+
+    fn test_close_persists_last_sequence(fs: Arc<dyn FileSystem>, db_path: PathBuf) {
+        let fm = FileManager::new_with_fs(db_path.clone(), fs.clone())
+            .expect("Failed to create database");
+        let mut allocated = 0;
+        for _ in 0..5 {
+            allocated = fm.next_sequence().expect("Sequence should be available");
+        }
+        assert_eq!(fm.new_file_number(), 2);
+        fm.close().expect("Close should succeed");
+
+        let fm =
+            FileManager::open_existing_with_fs(db_path, fs).expect("Failed to reopen database");
+        assert_eq!(fm.last_sequence(), allocated);
+        assert!(fm.next_sequence().unwrap() > allocated);
+        assert_eq!(fm.new_file_number(), 3);
+    }
+
+    fn test_persist_manifest_syncs_pending_dir_entries(fs: Arc<dyn FileSystem>, db_path: PathBuf) {
+        let mut fm = FileManager::new_with_fs(db_path.clone(), fs.clone())
+            .expect("Failed to create database");
+        fm.set_batch_dir_syncs(true);
+
+        let create = |fm: &FileManager| {
+            let path = fm.generate_filename(Name::SSTable, Some(fm.new_file_number()));
+            fs.create_new(&path).expect("Failed to create file");
+            fm.record_dir_change(path).unwrap();
+        };
+
+        create(&fm);
+        fm.persist_manifest().expect("Persist should succeed");
+        assert!(fm.pending_dir_entries.lock().unwrap().is_empty());
+
+        // close persists the manifest, so it syncs whatever is still pending first
+        create(&fm);
+        assert_eq!(fm.pending_dir_entries.lock().unwrap().len(), 1);
+        fm.close().expect("Close should succeed");
+
+        let fm =
+            FileManager::open_existing_with_fs(db_path, fs).expect("Failed to reopen database");
+        assert_eq!(fm.new_file_number(), 4);
+    }
+
+    fn write_file(fs: &dyn FileSystem, path: &Path, contents: &str) {
+        let mut file = fs.create(path).expect("Failed to create file");
+        file.write_all(contents.as_bytes())
+            .expect("Failed to write file");
+    }
+
+    fn test_open_fails_on_empty_current_file(fs: Arc<dyn FileSystem>, db_path: PathBuf) {
+        {
+            let _fm = FileManager::new_with_fs(db_path.clone(), fs.clone())
+                .expect("Failed to create database");
+        }
+
+        for contents in ["", "\n", "  \t \n"] {
+            write_file(fs.as_ref(), &db_path.join("CURRENT"), contents);
+
+            let err = FileManager::open_existing_with_fs(db_path.clone(), fs.clone()).unwrap_err();
+
+            assert_eq!(err.kind(), ErrorKind::InvalidData);
+            assert_eq!(err.to_string(), "CURRENT file is empty or corrupt");
+            assert!(
+                !fs.exists(&db_path.join("LOCK")),
+                "A failed open should not leave the LOCK behind"
+            );
+        }
+    }
+
+    fn test_last_sequence_recovered_from_manifest(fs: Arc<dyn FileSystem>, db_path: PathBuf) {
+        {
+            let fm = FileManager::new_with_fs(db_path.clone(), fs.clone())
+                .expect("Failed to create database");
+            assert_eq!(fm.next_sequence().unwrap(), 1);
+        }
+        write_file(
+            fs.as_ref(),
+            &db_path.join("MANIFEST-000001"),
+            "next_file_number: 7\nlast_sequence: 41\n",
+        );
+
+        let fm = FileManager::open_existing_with_fs(db_path.clone(), fs.clone())
+            .expect("Failed to open database");
+        assert_eq!(fm.last_sequence(), 41);
+        assert_eq!(fm.next_sequence().unwrap(), 42);
+        assert_eq!(fm.new_file_number(), 7);
+        drop(fm);
+
+        // Older manifests without a last_sequence line start from 0
+        write_file(
+            fs.as_ref(),
+            &db_path.join("MANIFEST-000001"),
+            "next_file_number: 7\n",
+        );
+        let fm = FileManager::open_existing_with_fs(db_path, fs).expect("Failed to open database");
+        assert_eq!(fm.next_sequence().unwrap(), 1);
+    }
+
+    fn test_open_upgrades_unversioned_manifest(fs: Arc<dyn FileSystem>, db_path: PathBuf) {
+        {
+            let fm =
+                FileManager::new_with_fs(db_path.clone(), fs.clone()).expect("Failed to create");
+            fm.close().expect("Failed to close");
+        }
+        write_file(
+            fs.as_ref(),
+            &db_path.join("MANIFEST-000001"),
+            "next_file_number: 6\nlast_sequence: 11\n",
+        );
+
+        let fm = FileManager::open_existing_with_fs(db_path.clone(), fs.clone())
+            .expect("Failed to open old format");
+
+        assert_eq!(fm.new_file_number(), 6);
+        assert_eq!(fm.last_sequence(), 11);
+        assert_eq!(
+            fs.read_to_string(&db_path.join("MANIFEST-000001")).unwrap(),
+            "format_version: 1\nnext_file_number: 6\nlast_sequence: 11\n"
+        );
+        assert!(!fs.exists(&db_path.join("MANIFEST-000001.tmp")));
+    }
+
+    fn test_open_rejects_newer_manifest_format(fs: Arc<dyn FileSystem>, db_path: PathBuf) {
+        {
+            let fm =
+                FileManager::new_with_fs(db_path.clone(), fs.clone()).expect("Failed to create");
+            fm.close().expect("Failed to close");
+        }
+        let newer = format!(
+            "format_version: {}\nnext_file_number: 2\nlast_sequence: 0\n",
+            MANIFEST_FORMAT_VERSION + 1
+        );
+        write_file(fs.as_ref(), &db_path.join("MANIFEST-000001"), &newer);
+
+        let err = FileManager::open_existing_with_fs(db_path.clone(), fs.clone()).unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::Unsupported);
+        assert!(err.to_string().contains("newer than the supported version"));
+        assert_eq!(
+            fs.read_to_string(&db_path.join("MANIFEST-000001")).unwrap(),
+            newer,
+            "A newer manifest must not be rewritten"
+        );
+        assert!(!fs.exists(&db_path.join("LOCK")));
+    }
+
+    fn test_open_rejects_newer_manifest_before_parsing_fields(
+        fs: Arc<dyn FileSystem>,
+        db_path: PathBuf,
+    ) {
+        {
+            let fm =
+                FileManager::new_with_fs(db_path.clone(), fs.clone()).expect("Failed to create");
+            fm.close().expect("Failed to close");
+        }
+        // A future format that no longer has a next_file_number line
+        write_file(
+            fs.as_ref(),
+            &db_path.join("MANIFEST-000001"),
+            "format_version: 2\nfile_counter: 2\n",
+        );
+
+        let err = FileManager::open_existing_with_fs(db_path, fs).unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::Unsupported);
+        assert!(err.to_string().contains("newer than the supported version"));
+    }
+
+    fn test_open_or_create_creates_missing_database(fs: Arc<dyn FileSystem>, db_path: PathBuf) {
+        let fm = FileManager::open_or_create_with_fs(db_path.clone(), fs.clone())
+            .expect("Failed to create database");
+
+        assert!(fs.exists(&db_path.join("CURRENT")));
+        assert!(fs.exists(&db_path.join("LOCK")));
+        assert_eq!(fm.new_file_number(), 2);
+    }
+
+    fn test_open_or_create_opens_existing_database(fs: Arc<dyn FileSystem>, db_path: PathBuf) {
+        {
+            let fm = FileManager::open_or_create_with_fs(db_path.clone(), fs.clone())
+                .expect("Failed to create");
+            fm.close().expect("Failed to close");
+        }
+        write_file(
+            fs.as_ref(),
+            &db_path.join("MANIFEST-000001"),
+            "next_file_number: 9\nlast_sequence: 3\n",
+        );
+
+        let fm = FileManager::open_or_create_with_fs(db_path, fs).expect("Failed to open database");
+
+        assert_eq!(fm.new_file_number(), 9, "Existing manifest should be used");
+        assert_eq!(fm.last_sequence(), 3);
+    }
+
+    fn test_open_or_create_rejects_foreign_directory(fs: Arc<dyn FileSystem>, db_path: PathBuf) {
+        fs.create_dir_all(&db_path)
+            .expect("Failed to create directory");
+        write_file(fs.as_ref(), &db_path.join("notes.txt"), "not a db");
+
+        let err = FileManager::open_or_create_with_fs(db_path.clone(), fs.clone()).unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+        assert!(
+            err.to_string()
+                .contains("does not contain a taurusdb database")
+        );
+        assert!(
+            !fs.exists(&db_path.join("CURRENT")),
+            "Nothing should be written"
+        );
+    }
+
+    fn test_lock_cleanup_on_drop(fs: Arc<dyn FileSystem>, db_path: PathBuf) {
+        {
+            let _fm = FileManager::new_with_fs(db_path.clone(), fs.clone())
+                .expect("Failed to create database");
+            // LOCK should exist here
+            assert!(
+                fs.exists(&db_path.join("LOCK")),
+                "LOCK should exist while FileManager is alive"
+            );
+        } // fm dropped here - Drop runs
+
+        // LOCK should be deleted
+        assert!(
+            !fs.exists(&db_path.join("LOCK")),
+            "LOCK should be removed after FileManager is dropped"
+        );
+    }
+
+    fn test_persistence_across_restarts(fs: Arc<dyn FileSystem>, db_path: PathBuf) {
+        // Create database and get some file numbers
+        {
+            let fm = FileManager::new_with_fs(db_path.clone(), fs.clone())
+                .expect("Failed to create database");
+            assert_eq!(fm.new_file_number(), 2);
+
+            assert_eq!(fm.new_file_number(), 3);
+            assert_eq!(fm.new_file_number(), 4);
+            // Counter is now at 5
+        }
+
+        // Reopen database
+        let fm = FileManager::open_existing_with_fs(db_path, fs).expect("Failed to open database");
+
+        // Next file number should continue from where we left off
+        // Wait, this won't work! The manifest still says next_file_number: 2
+
+        // because we never updated it. This is expected behavior for Phase 1.
+        // In Phase 2+, you'd update the manifest when creating files.
+
+        // For now, this test verifies that open_existing reads the manifest correctly
+        assert_eq!(
+            fm.new_file_number(),
+            2,
+            "After reopen, should start from manifest value (2)"
+        );
+    }
+
+    fn test_atomic_current_file_creation(fs: Arc<dyn FileSystem>, db_path: PathBuf) {
+        let _fm = FileManager::new_with_fs(db_path.clone(), fs.clone())
+            .expect("Failed to create database");
+
+        // Verify CURRENT.tmp doesn't exist (cleaned up)
+        assert!(
+            !fs.exists(&db_path.join("CURRENT.tmp")),
+            "CURRENT.tmp should be cleaned up after atomic rename"
+        );
+
+        // Verify CURRENT exists
+        assert!(fs.exists(&db_path.join("CURRENT")), "CURRENT should exist");
+    }
+
+    fn test_batched_dir_sync(fs: Arc<dyn FileSystem>, db_path: PathBuf) {
+        let mut fm = FileManager::new_with_fs(db_path.clone(), fs.clone())
+            .expect("Failed to create database");
+        fm.set_batch_dir_syncs(true);
+
+        assert_eq!(fm.sync_dir_pending().unwrap(), 0, "Nothing pending yet");
+
+        let mut created = Vec::new();
+        for _ in 0..5 {
+            let path = fm.generate_filename(Name::SSTable, Some(fm.new_file_number()));
+            write_file(fs.as_ref(), &path, "data");
+            fm.record_dir_change(path.clone()).unwrap();
+            created.push(path);
+        }
+
+        assert_eq!(fm.pending_dir_entries.lock().unwrap().len(), 5);
+        assert_eq!(fm.sync_dir_pending().unwrap(), 5);
+        assert!(fm.pending_dir_entries.lock().unwrap().is_empty());
+
+        for path in created {
+            assert!(fs.exists(&path), "{:?} should be present after sync", path);
+        }
+    }
+
+    fn test_unbatched_dir_sync_is_immediate(fs: Arc<dyn FileSystem>, db_path: PathBuf) {
+        let fm = FileManager::new_with_fs(db_path.clone(), fs.clone())
+            .expect("Failed to create database");
+
+        let path = fm.generate_filename(Name::SSTable, Some(fm.new_file_number()));
+        write_file(fs.as_ref(), &path, "data");
+        fm.record_dir_change(path).unwrap();
+
+        assert_eq!(
+            fm.sync_dir_pending().unwrap(),
+            0,
+            "Nothing should be left pending"
+        );
+    }
+
+    fn test_close_surfaces_lock_removal_error(fs: Arc<dyn FileSystem>, db_path: PathBuf) {
+        let fm = FileManager::new_with_fs(db_path.clone(), fs.clone())
+            .expect("Failed to create database");
+        fs.remove_file(&db_path.join("LOCK"))
+            .expect("Failed to remove LOCK");
+
+        let result = fm.close();
+
+        assert!(result.is_err(), "Close should report the missing LOCK");
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_advisory_lock_unsupported_in_memory() {
+        let (fs, db_path, _) = setup_in_memory();
+        FileManager::new_with_fs(db_path.clone(), fs.clone())
+            .expect("Failed to create database")
+            .close()
+            .expect("Failed to close");
+
+        let err =
+            FileManager::open_existing_with_lock_mode(db_path, fs, LockMode::Advisory).unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::Unsupported);
+    }
+
+    // The advisory tests read the LOCK file while it is held, which Windows' mandatory locks don't
+    // allow
+    #[cfg(unix)]
+    fn open_advisory(db_path: &Path) -> Result<FileManager, Error> {
+        FileManager::open_existing_with_lock_mode(
+            db_path.to_path_buf(),
+            Arc::new(StdFs),
+            LockMode::Advisory,
+        )
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_advisory_lock_blocks_second_open_until_dropped() {
+        let temp_dir = setup_temp_dir();
+        let db_path = temp_dir.path().to_path_buf();
+        FileManager::new(db_path.clone())
+            .expect("Failed to create")
+            .close()
+            .expect("Failed to close");
+
+        let fm1 = open_advisory(&db_path).expect("Failed to open with advisory lock");
+        assert_eq!(
+            fs::read_to_string(db_path.join("LOCK")).unwrap(),
+            std::process::id().to_string()
+        );
+
+        let err = open_advisory(&db_path).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+        assert!(err.to_string().contains("already open by process"));
+
+        drop(fm1);
+        assert!(
+            db_path.join("LOCK").exists(),
+            "Advisory mode leaves the LOCK file in place"
+        );
+
+        let fm2 = open_advisory(&db_path).expect("Lock should be released on drop");
+        fm2.close().expect("Failed to close");
+        open_advisory(&db_path).expect("Lock should be released on close");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_advisory_create_blocks_second_open() {
+        let temp_dir = setup_temp_dir();
+        let db_path = temp_dir.path().to_path_buf();
+
+        let fm =
+            FileManager::new_with_lock_mode(db_path.clone(), Arc::new(StdFs), LockMode::Advisory)
+                .expect("Failed to create with advisory lock");
+
+        let err = open_advisory(&db_path).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+
+        let err = FileManager::open_or_create_with_lock_mode(
+            db_path.clone(),
+            Arc::new(StdFs),
+            LockMode::Advisory,
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+
+        drop(fm);
+        open_advisory(&db_path).expect("Lock should be released on drop");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_advisory_lock_ignores_stale_lock_file() {
+        let temp_dir = setup_temp_dir();
+        let db_path = temp_dir.path().to_path_buf();
+        FileManager::new(db_path.clone())
+            .expect("Failed to create")
+            .close()
+            .expect("Failed to close");
+
+        // A crashed existence-locked process leaves its LOCK file behind
+        fs::write(db_path.join("LOCK"), "99999999").expect("Failed to write LOCK");
+
+        open_advisory(&db_path).expect("A LOCK file nobody holds should not block");
+        assert_eq!(
+            fs::read_to_string(db_path.join("LOCK")).unwrap(),
+            std::process::id().to_string()
+        );
+    }
+
     #[test]
     fn test_file_number_generation_sequential() {
         let temp_dir = setup_temp_dir();
@@ -543,6 +1343,60 @@ mod tests {
         assert_eq!(path, db_path.join("LOCK"), "Lock filename should be LOCK");
     }
 
+    #[test]
+    fn test_filename_round_trip_past_six_digits() {
+        let temp_dir = setup_temp_dir();
+        let fm = FileManager::new(temp_dir.path().to_path_buf()).expect("Failed to create");
+
+        for number in [1, 999_999, 1_000_000, u64::MAX] {
+            for name in [Name::SSTable, Name::WriteAheadLog, Name::Manifest] {
+                let path = fm.generate_filename(name, Some(number));
+                let file_name = path.file_name().unwrap().to_str().unwrap();
+
+                assert_eq!(
+                    parse_filename(file_name),
+                    Some((name, Some(number))),
+                    "{file_name} should round trip"
+                );
+            }
+        }
+
+        assert_eq!(
+            fm.generate_filename(Name::SSTable, Some(1_000_000)),
+            temp_dir.path().join("1000000.sst")
+        );
+        assert_eq!(
+            fm.generate_filename(Name::WriteAheadLog, Some(u64::MAX)),
+            temp_dir.path().join("18446744073709551615.log")
+        );
+
+        for name in [Name::Current, Name::Lock] {
+            let path = fm.generate_filename(name, None);
+            let file_name = path.file_name().unwrap().to_str().unwrap();
+            assert_eq!(parse_filename(file_name), Some((name, None)));
+        }
+    }
+
+    #[test]
+    fn test_parse_filename_rejects_unknown_names() {
+        for file_name in [
+            "42.sst",
+            "00004a.sst",
+            "+00042.log",
+            "18446744073709551616.sst",
+            "MANIFEST-",
+            "CURRENT.tmp",
+            "000002.log.corrupt",
+            "notes.txt",
+        ] {
+            assert_eq!(
+                parse_filename(file_name),
+                None,
+                "{file_name} should not parse"
+            );
+        }
+    }
+
     #[test]
 
     fn test_generate_filename_zero_padding() {
@@ -600,75 +1454,4 @@ mod tests {
         // This should panic
         fm.generate_filename(Name::Current, Some(42));
     }
-
-    #[test]
-    fn test_lock_cleanup_on_drop() {
-        let temp_dir = setup_temp_dir();
-        let db_path = temp_dir.path().to_path_buf();
-
-        {
-            let _fm = FileManager::new(db_path.clone()).expect("Failed to create database");
-            // LOCK should exist here
-            assert!(
-                db_path.join("LOCK").exists(),
-                "LOCK should exist while FileManager is alive"
-            );
-        } // fm dropped here - Drop runs
-
-        // LOCK should be deleted
-        assert!(
-            !db_path.join("LOCK").exists(),
-            "LOCK should be removed after FileManager is dropped"
-        );
-    }
-
-    #[test]
-    fn test_persistence_across_restarts() {
-        let temp_dir = setup_temp_dir();
-        let db_path = temp_dir.path().to_path_buf();
-
-        // Create database and get some file numbers
-        {
-            let fm = FileManager::new(db_path.clone()).expect("Failed to create database");
-            assert_eq!(fm.new_file_number(), 2);
-
-            assert_eq!(fm.new_file_number(), 3);
-            assert_eq!(fm.new_file_number(), 4);
-            // Counter is now at 5
-        }
-
-        // Reopen database
-        let fm = FileManager::open_existing(db_path).expect("Failed to open database");
-
-        // Next file number should continue from where we left off
-        // Wait, this won't work! The manifest still says next_file_number: 2
-
-        // because we never updated it. This is expected behavior for Phase 1.
-        // In Phase 2+, you'd update the manifest when creating files.
-
-        // For now, this test verifies that open_existing reads the manifest correctly
-        assert_eq!(
-            fm.new_file_number(),
-            2,
-            "After reopen, should start from manifest value (2)"
-        );
-    }
-
-    #[test]
-
-    fn test_atomic_current_file_creation() {
-        let temp_dir = setup_temp_dir();
-        let db_path = temp_dir.path().to_path_buf();
-
-        let _fm = FileManager::new(db_path.clone()).expect("Failed to create database");
-
-        // Verify CURRENT.tmp doesn't exist (cleaned up)
-        assert!(
-            !db_path.join("CURRENT.tmp").exists(),
-            "CURRENT.tmp should be cleaned up after atomic rename"
-        );
-
-        // Verify CURRENT exists
-        assert!(db_path.join("CURRENT").exists(), "CURRENT should exist");
-    }
 }
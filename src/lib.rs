@@ -3,6 +3,8 @@ mod db;
 mod errors;
 mod file_manager;
 mod memtable;
+mod metrics;
 mod skiplist;
 mod storage;
+mod vfs;
 mod wal;
@@ -1,7 +1,8 @@
 use std::io::Error;
 
-use crate::skiplist::{self, SkipList, SkipListIter};
-use crate::storage::internal_key::InternalKey;
+use crate::skiplist::{SkipList, SkipListIter, SkipListRevIter};
+use crate::storage::internal_key::{InternalKey, KeyType};
+use crate::wal::WalRecord;
 
 pub struct MemTable {
     skiplist: SkipList,
@@ -24,15 +25,63 @@ impl MemTable {
         let value_size = value.len();
         let overhead = 64;
 
+        // An exact match overwrites in place, so only the value size changes
+        if let Some(old_size) = self.skiplist.with_value(&key, |v| v.len()) {
+            self.skiplist.insert(key, value)?;
+            self.size_bytes = self.size_bytes - old_size + value_size;
+            return Ok(());
+        }
+
         self.skiplist.insert(key, value)?;
 
         self.size_bytes += key_size + value_size + overhead;
 
         Ok(())
     }
+    // Applies records in log order. If two records share the same InternalKey
+    // the later one wins. Returns how many records collided with an earlier one
+    // so the caller can report the duplicates.
+    pub fn replay<I>(&mut self, records: I) -> Result<usize, Error>
+    where
+        I: IntoIterator<Item = WalRecord>,
+    {
+        let mut duplicates = 0;
+        for record in records {
+            let (key, value) = match record {
+                WalRecord::Put(key, value) => (key, value),
+                WalRecord::Delete(key) => (key, Vec::new()),
+            };
+            if self.skiplist.contains_key(&key) {
+                duplicates += 1;
+            }
+            self.put(key, value)?;
+        }
+        Ok(duplicates)
+    }
     pub fn get(&self, key: &InternalKey) -> Option<Vec<u8>> {
         self.skiplist.get(key)
     }
+    pub fn contains_key(&self, key: &InternalKey) -> bool {
+        self.skiplist.contains_key(key)
+    }
+    pub fn with_value<F, R>(&self, key: &InternalKey, f: F) -> Option<R>
+    where
+        F: FnOnce(&[u8]) -> R,
+    {
+        self.skiplist.with_value(key, f)
+    }
+    // Records a delete as a Delete-typed entry with an empty value. Unlike delete, older versions
+    // stay in place so the tombstone can shadow them, and values in lower levels, after a flush.
+    pub fn put_tombstone(&mut self, user_key: Vec<u8>, sequence_number: u64) -> Result<(), Error> {
+        self.put(
+            InternalKey::new(user_key, sequence_number, KeyType::Delete),
+            Vec::new(),
+        )
+    }
+    pub fn get_entry(&self, key: &InternalKey) -> Option<(KeyType, Vec<u8>)> {
+        self.skiplist.get_entry(key)
+    }
+    // Physically removes the node, for GC and tests. Regular deletes should use put_tombstone.
     pub fn delete(&mut self, key: InternalKey) -> bool {
         let value = self.get(&key);
         if let Some(value) = value {
@@ -47,7 +96,157 @@ impl MemTable {
     pub fn size(&self) -> usize {
         self.size_bytes
     }
-    pub fn iter(&self) -> SkipListIter {
+    pub fn iter(&self) -> SkipListIter<'_> {
         self.skiplist.iter()
     }
+    pub fn seek(&self, key: &InternalKey) -> SkipListIter<'_> {
+        self.skiplist.seek(key)
+    }
+    pub fn iter_rev(&self) -> SkipListRevIter<'_> {
+        self.skiplist.iter_rev()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_key(s: &str, seq: u64) -> InternalKey {
+        InternalKey::new(s.as_bytes().to_vec(), seq, KeyType::Put)
+    }
+
+    #[test]
+    fn test_tombstone_keeps_older_versions() {
+        let mut memtable = MemTable::new(1024 * 1024);
+
+        memtable.put(make_key("key", 1), b"value".to_vec()).unwrap();
+        memtable.put_tombstone(b"key".to_vec(), 2).unwrap();
+
+        let tombstone = InternalKey::new(b"key".to_vec(), 2, KeyType::Delete);
+        assert_eq!(
+            memtable.get_entry(&tombstone),
+            Some((KeyType::Delete, Vec::new()))
+        );
+        assert_eq!(memtable.get(&make_key("key", 1)), Some(b"value".to_vec()));
+
+        // Newest version first, so a reader or flush sees the tombstone before the value it shadows
+        let keys: Vec<InternalKey> = memtable.iter().map(|(k, _)| k).collect();
+        assert_eq!(keys, vec![tombstone, make_key("key", 1)]);
+    }
+
+    #[test]
+    fn test_equal_sequence_tombstone_wins_regardless_of_insert_order() {
+        for tombstone_first in [true, false] {
+            let mut memtable = MemTable::new(1024 * 1024);
+
+            if tombstone_first {
+                memtable.put_tombstone(b"key".to_vec(), 5).unwrap();
+                memtable.put(make_key("key", 5), b"value".to_vec()).unwrap();
+            } else {
+                memtable.put(make_key("key", 5), b"value".to_vec()).unwrap();
+                memtable.put_tombstone(b"key".to_vec(), 5).unwrap();
+            }
+
+            // Both records are kept, and the first one a reader reaches is the tombstone
+            let entries: Vec<(InternalKey, Vec<u8>)> = memtable.iter().collect();
+            assert_eq!(entries.len(), 2);
+            assert_eq!(entries[0].0.key_type, KeyType::Delete);
+            assert_eq!(entries[1], (make_key("key", 5), b"value".to_vec()));
+        }
+    }
+
+    // The newest version of user_key visible at snapshot_seq, None if there is none
+    fn visible_at(memtable: &MemTable, user_key: &[u8], snapshot_seq: u64) -> Option<InternalKey> {
+        memtable
+            .seek(&InternalKey::for_lookup(user_key, snapshot_seq))
+            .next()
+            .map(|(k, _)| k)
+            .filter(|k| k.user_key == user_key)
+    }
+
+    #[test]
+    fn test_lookup_key_lands_on_visible_version() {
+        let mut memtable = MemTable::new(1024 * 1024);
+        memtable.put(make_key("a", 9), b"other".to_vec()).unwrap();
+        memtable.put(make_key("key", 2), b"v2".to_vec()).unwrap();
+        memtable.put(make_key("key", 5), b"v5".to_vec()).unwrap();
+        memtable.put_tombstone(b"key".to_vec(), 8).unwrap();
+        memtable.put(make_key("key", 10), b"v10".to_vec()).unwrap();
+        memtable.put(make_key("kez", 1), b"next".to_vec()).unwrap();
+
+        let seq_of = |snapshot| visible_at(&memtable, b"key", snapshot).map(|k| k.sequence_number);
+
+        assert_eq!(seq_of(1), None);
+        assert_eq!(seq_of(2), Some(2));
+        assert_eq!(seq_of(4), Some(2));
+        assert_eq!(seq_of(5), Some(5));
+        assert_eq!(seq_of(9), Some(8));
+        assert_eq!(seq_of(u64::MAX), Some(10));
+
+        let at_8 = visible_at(&memtable, b"key", 8).unwrap();
+        assert!(at_8.is_deletion());
+        assert_eq!(visible_at(&memtable, b"ke", u64::MAX), None);
+    }
+
+    #[test]
+    fn test_lookup_key_prefers_tombstone_at_equal_sequence() {
+        let mut memtable = MemTable::new(1024 * 1024);
+        memtable.put(make_key("key", 5), b"value".to_vec()).unwrap();
+        memtable.put_tombstone(b"key".to_vec(), 5).unwrap();
+
+        assert!(visible_at(&memtable, b"key", 5).unwrap().is_deletion());
+    }
+
+    #[test]
+    fn test_tombstone_counts_toward_size() {
+        let mut memtable = MemTable::new(1024 * 1024);
+
+        memtable.put_tombstone(b"key".to_vec(), 1).unwrap();
+
+        assert!(memtable.size() > 0);
+    }
+
+    #[test]
+    fn test_physical_delete_still_removes_node() {
+        let mut memtable = MemTable::new(1024 * 1024);
+        memtable.put(make_key("key", 1), b"value".to_vec()).unwrap();
+
+        assert!(memtable.delete(make_key("key", 1)));
+        assert_eq!(memtable.iter().count(), 0);
+        assert_eq!(memtable.size(), 0);
+    }
+
+    #[test]
+    fn test_iter_rev_is_exact_reverse_of_iter() {
+        let mut memtable = MemTable::new(1024 * 1024);
+        memtable.put(make_key("a", 3), b"a3".to_vec()).unwrap();
+        memtable.put(make_key("key", 2), b"v2".to_vec()).unwrap();
+        memtable.put(make_key("key", 7), b"v7".to_vec()).unwrap();
+        memtable.put_tombstone(b"key".to_vec(), 5).unwrap();
+        memtable.put(make_key("key", 5), b"v5".to_vec()).unwrap();
+        memtable.put(make_key("z", 1), b"z1".to_vec()).unwrap();
+
+        let forward: Vec<(InternalKey, Vec<u8>)> = memtable.iter().collect();
+        let mut backward: Vec<(InternalKey, Vec<u8>)> = memtable.iter_rev().collect();
+        backward.reverse();
+
+        assert_eq!(forward.len(), 6);
+        assert_eq!(backward, forward);
+
+        // Oldest version of "key" first, and the value at seq 5 before the tombstone that shadows it
+        let key_versions: Vec<(u64, KeyType)> = memtable
+            .iter_rev()
+            .filter(|(k, _)| k.user_key == b"key")
+            .map(|(k, _)| (k.sequence_number, k.key_type))
+            .collect();
+        assert_eq!(
+            key_versions,
+            vec![
+                (2, KeyType::Put),
+                (5, KeyType::Put),
+                (5, KeyType::Delete),
+                (7, KeyType::Put),
+            ]
+        );
+    }
 }
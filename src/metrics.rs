@@ -0,0 +1,240 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, MutexGuard},
+};
+
+// Bucket i holds values in [2^(i-1), 2^i), bucket 0 holds zero
+const HISTOGRAM_BUCKETS: usize = 65;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    buckets: [u64; HISTOGRAM_BUCKETS],
+    count: u64,
+    sum: u64,
+    min: u64,
+    max: u64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Histogram {
+            buckets: [0; HISTOGRAM_BUCKETS],
+            count: 0,
+            sum: 0,
+            min: u64::MAX,
+            max: 0,
+        }
+    }
+}
+
+impl Histogram {
+    fn bucket_for(value: u64) -> usize {
+        (u64::BITS - value.leading_zeros()) as usize
+    }
+
+    fn bucket_upper_bound(bucket: usize) -> u64 {
+        match bucket {
+            0 => 0,
+            64 => u64::MAX,
+            b => (1u64 << b) - 1,
+        }
+    }
+
+    pub fn record(&mut self, value: u64) {
+        self.buckets[Self::bucket_for(value)] += 1;
+        self.count += 1;
+        self.sum = self.sum.saturating_add(value);
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn sum(&self) -> u64 {
+        self.sum
+    }
+
+    pub fn min(&self) -> Option<u64> {
+        (self.count > 0).then_some(self.min)
+    }
+
+    pub fn max(&self) -> Option<u64> {
+        (self.count > 0).then_some(self.max)
+    }
+
+    // Returns an upper bound for the value at percentile p (0.0..=100.0). Values are bucketed by
+    // power of two, so the result is within 2x of the true value and never above the recorded max.
+    pub fn percentile(&self, p: f64) -> Option<u64> {
+        if self.count == 0 {
+            return None;
+        }
+
+        let rank = ((p.clamp(0.0, 100.0) / 100.0) * self.count as f64).ceil() as u64;
+        let rank = rank.max(1);
+
+        let mut seen = 0;
+        for (bucket, &n) in self.buckets.iter().enumerate() {
+            seen += n;
+            if seen >= rank {
+                return Some(Self::bucket_upper_bound(bucket).clamp(self.min, self.max));
+            }
+        }
+        Some(self.max)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MetricsSnapshot {
+    pub counters: HashMap<String, u64>,
+    pub histograms: HashMap<String, Histogram>,
+}
+
+impl MetricsSnapshot {
+    pub fn counter(&self, name: &str) -> u64 {
+        self.counters.get(name).copied().unwrap_or(0)
+    }
+
+    pub fn histogram(&self, name: &str) -> Option<&Histogram> {
+        self.histograms.get(name)
+    }
+}
+
+// Counters and histograms registered by name, e.g. "wal.bytes_written". Everything sits behind
+// one lock so a snapshot sees every metric at the same point in time.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    state: Mutex<MetricsSnapshot>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Metrics are recorded on the write path, so a panic elsewhere while holding the lock must not
+    // turn every later write into a panic too. The counters are still usable after one.
+    fn state(&self) -> MutexGuard<'_, MetricsSnapshot> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn increment(&self, name: &str, by: u64) {
+        let mut state = self.state();
+        match state.counters.get_mut(name) {
+            Some(counter) => *counter = counter.saturating_add(by),
+            None => {
+                state.counters.insert(name.to_string(), by);
+            }
+        }
+    }
+
+    pub fn record(&self, name: &str, value: u64) {
+        let mut state = self.state();
+        match state.histograms.get_mut(name) {
+            Some(histogram) => histogram.record(value),
+            None => {
+                let mut histogram = Histogram::default();
+                histogram.record(value);
+                state.histograms.insert(name.to_string(), histogram);
+            }
+        }
+    }
+
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        self.state().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counters_accumulate_by_name() {
+        let metrics = Metrics::new();
+
+        metrics.increment("wal.records", 1);
+        metrics.increment("wal.records", 2);
+        metrics.increment("wal.bytes_written", 100);
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.counter("wal.records"), 3);
+        assert_eq!(snapshot.counter("wal.bytes_written"), 100);
+        assert_eq!(snapshot.counter("missing"), 0);
+    }
+
+    #[test]
+    fn test_snapshot_is_not_affected_by_later_updates() {
+        let metrics = Metrics::new();
+        metrics.increment("flush.count", 1);
+
+        let snapshot = metrics.snapshot();
+        metrics.increment("flush.count", 1);
+
+        assert_eq!(snapshot.counter("flush.count"), 1);
+        assert_eq!(metrics.snapshot().counter("flush.count"), 2);
+    }
+
+    #[test]
+    fn test_histogram_percentiles() {
+        let metrics = Metrics::new();
+        for latency in 1..=100 {
+            metrics.record("get.latency_us", latency);
+        }
+
+        let snapshot = metrics.snapshot();
+        let histogram = snapshot.histogram("get.latency_us").unwrap();
+
+        assert_eq!(histogram.count(), 100);
+        assert_eq!(histogram.sum(), 5050);
+        assert_eq!(histogram.min(), Some(1));
+        assert_eq!(histogram.max(), Some(100));
+
+        // Percentiles are bucket upper bounds, so within 2x of the exact value
+        let p50 = histogram.percentile(50.0).unwrap();
+        assert!((50..100).contains(&p50), "p50 was {p50}");
+        assert_eq!(histogram.percentile(100.0), Some(100));
+        assert_eq!(histogram.percentile(0.0), Some(1));
+    }
+
+    #[test]
+    fn test_poisoned_lock_does_not_panic_recording() {
+        let metrics = std::sync::Arc::new(Metrics::new());
+        metrics.increment("wal.records", 1);
+
+        let poisoner = metrics.clone();
+        let _ = std::thread::spawn(move || {
+            let _guard = poisoner.state.lock().unwrap();
+            panic!("poison the metrics lock");
+        })
+        .join();
+        assert!(metrics.state.is_poisoned());
+
+        metrics.increment("wal.records", 1);
+        metrics.record("wal.append_us", 5);
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.counter("wal.records"), 2);
+        assert_eq!(snapshot.histogram("wal.append_us").unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_empty_histogram() {
+        let histogram = Histogram::default();
+
+        assert_eq!(histogram.percentile(50.0), None);
+        assert_eq!(histogram.min(), None);
+        assert_eq!(histogram.max(), None);
+    }
+
+    #[test]
+    fn test_histogram_extreme_values() {
+        let mut histogram = Histogram::default();
+        histogram.record(0);
+        histogram.record(u64::MAX);
+
+        assert_eq!(histogram.percentile(50.0), Some(0));
+        assert_eq!(histogram.percentile(99.0), Some(u64::MAX));
+        assert_eq!(histogram.sum(), u64::MAX);
+    }
+}
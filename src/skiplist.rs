@@ -1,7 +1,9 @@
-use crate::storage::internal_key::InternalKey;
+use crate::storage::internal_key::{InternalKey, KeyType};
 use rand::Rng;
-use std::cell::RefCell;
+use std::cell::{RefCell, RefMut};
 use std::io::Error;
+use std::marker::PhantomData;
+use std::ops::Deref;
 use std::rc::Rc;
 
 type NodePtr = Rc<RefCell<Node>>;
@@ -29,11 +31,15 @@ impl Node {
 
 const MAX_HEIGHT: usize = 12;
 
-pub struct SkipListIter {
+// The iterator holds a shared borrow of the SkipList it came from, so insert/delete (which take
+// &mut self) will not compile while an iterator is alive. Without this the iterator's cloned Rc
+// pointers could walk nodes that are being relinked underneath it.
+pub struct SkipListIter<'a> {
     current: Option<NodePtr>,
+    _list: PhantomData<&'a SkipList>,
 }
 
-impl Iterator for SkipListIter {
+impl Iterator for SkipListIter<'_> {
     type Item = (InternalKey, Vec<u8>);
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -55,11 +61,51 @@ impl Iterator for SkipListIter {
     }
 }
 
+// Nodes only have forward pointers, so reverse iteration collects the level 0 chain up front and
+// pops from the end. That is O(n) time and pointer-sized memory before the first item is yielded,
+// values are still only cloned as they are returned.
+pub struct SkipListRevIter<'a> {
+    nodes: Vec<NodePtr>,
+    _list: PhantomData<&'a SkipList>,
+}
+
+impl Iterator for SkipListRevIter<'_> {
+    type Item = (InternalKey, Vec<u8>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.nodes.pop()?;
+        let borrowed = node.borrow();
+        let key = borrowed.key.as_ref().unwrap().clone();
+        let value = borrowed.value.as_ref().unwrap().clone();
+
+        Some((key, value))
+    }
+}
+
+// Borrow of the SkipList's reusable search path. Clearing on drop keeps the buffer's capacity but
+// releases the node pointers, so a finished search never keeps unlinked nodes alive.
+struct SearchPath<'a>(RefMut<'a, Vec<NodePtr>>);
+
+impl Deref for SearchPath<'_> {
+    type Target = Vec<NodePtr>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Drop for SearchPath<'_> {
+    fn drop(&mut self) {
+        self.0.clear();
+    }
+}
+
 #[derive(Debug)]
 pub struct SkipList {
     head_node: NodePtr,
     current_max_level: usize,
     length: usize,
+    search_path: RefCell<Vec<NodePtr>>,
 }
 
 impl SkipList {
@@ -77,17 +123,44 @@ impl SkipList {
             })),
             current_max_level: 0,
             length: 0,
+            search_path: RefCell::new(Vec::with_capacity(MAX_HEIGHT)),
         }
     }
 
-    pub fn iter(&self) -> SkipListIter {
+    pub fn iter(&self) -> SkipListIter<'_> {
         SkipListIter {
             current: self.head_node.borrow().forward_pointers[0].clone(),
+            _list: PhantomData,
         }
     }
 
-    fn search(&self, key: &InternalKey) -> Vec<NodePtr> {
-        let mut update: Vec<NodePtr> = Vec::with_capacity(MAX_HEIGHT);
+    // Iterates from the first entry >= key
+    pub fn seek(&self, key: &InternalKey) -> SkipListIter<'_> {
+        let current = self.search(key)[0].borrow().forward_pointers[0].clone();
+        SkipListIter {
+            current,
+            _list: PhantomData,
+        }
+    }
+
+    pub fn iter_rev(&self) -> SkipListRevIter<'_> {
+        let mut nodes = Vec::with_capacity(self.length);
+        let mut current = self.head_node.borrow().forward_pointers[0].clone();
+
+        while let Some(node) = current {
+            current = node.borrow().forward_pointers[0].clone();
+            nodes.push(node);
+        }
+
+        SkipListRevIter {
+            nodes,
+            _list: PhantomData,
+        }
+    }
+
+    fn search(&self, key: &InternalKey) -> SearchPath<'_> {
+        let mut update = self.search_path.borrow_mut();
+        update.clear();
         let mut current = Rc::clone(&self.head_node);
 
         for level in (0..=self.current_max_level).rev() {
@@ -116,7 +189,7 @@ impl SkipList {
         }
 
         update.reverse();
-        update
+        SearchPath(update)
     }
 
     pub fn insert(&mut self, key: InternalKey, value: Vec<u8>) -> Result<(), Error> {
@@ -144,6 +217,7 @@ impl SkipList {
                 update[level].borrow().forward_pointers[level].clone();
             update[level].borrow_mut().forward_pointers[level] = Some(Rc::clone(&new_node));
         });
+        drop(update);
 
         if height > self.current_max_level + 1 {
             for level in (self.current_max_level + 1)..height {
@@ -158,8 +232,7 @@ impl SkipList {
     }
 
     pub fn get(&self, key: &InternalKey) -> Option<Vec<u8>> {
-        let update = self.search(key);
-        let current = update[0].clone();
+        let current = self.search(key)[0].clone();
 
         if let Some(next_node) = &current.borrow().forward_pointers[0] {
             let nn = next_node.borrow();
@@ -172,6 +245,51 @@ impl SkipList {
         None
     }
 
+    pub fn contains_key(&self, key: &InternalKey) -> bool {
+        let current = self.search(key)[0].clone();
+
+        if let Some(next_node) = &current.borrow().forward_pointers[0] {
+            return next_node.borrow().key.as_ref() == Some(key);
+        }
+
+        false
+    }
+
+    // Hands the caller a borrow of the stored value instead of cloning it, the borrow only lives
+    // for the duration of the closure.
+    pub fn with_value<F, R>(&self, key: &InternalKey, f: F) -> Option<R>
+    where
+        F: FnOnce(&[u8]) -> R,
+    {
+        let current = self.search(key)[0].clone();
+
+        if let Some(next_node) = &current.borrow().forward_pointers[0] {
+            let nn = next_node.borrow();
+            if nn.key.as_ref() == Some(key) {
+                return nn.value.as_deref().map(f);
+            }
+        }
+
+        None
+    }
+
+    // Like get, but also reports whether the stored entry is a tombstone, so a Delete entry can be
+    // told apart from a live empty value.
+    pub fn get_entry(&self, key: &InternalKey) -> Option<(KeyType, Vec<u8>)> {
+        let current = self.search(key)[0].clone();
+
+        if let Some(next_node) = &current.borrow().forward_pointers[0] {
+            let nn = next_node.borrow();
+            if let (Some(found), Some(value)) = (nn.key.as_ref(), nn.value.as_ref())
+                && found == key
+            {
+                return Some((found.key_type, value.clone()));
+            }
+        }
+
+        None
+    }
+
     pub fn delete(&mut self, key: &InternalKey) -> bool {
         let update = self.search(key);
         let current = update[0].clone();
@@ -191,6 +309,7 @@ impl SkipList {
                 update[level].borrow_mut().forward_pointers[level] =
                     ntd.borrow().forward_pointers[level].clone();
             });
+            drop(update);
 
             while self.current_max_level > 0
                 && self.head_node.borrow().forward_pointers[self.current_max_level].is_none()
@@ -215,6 +334,15 @@ impl SkipList {
         height
     }
 
+    // Versions of a user key at different sequences are distinct keys, so both are kept and the
+    // higher sequence sorts first. An exact InternalKey match takes other's value.
+    pub fn merge_from(&mut self, other: SkipList) -> Result<(), Error> {
+        for (key, value) in other.iter() {
+            self.insert(key, value)?;
+        }
+        Ok(())
+    }
+
     pub fn len(&self) -> usize {
         self.length
     }
@@ -500,6 +628,98 @@ mod tests {
         assert_eq!(sl.get(&key), Some(value));
     }
 
+    #[test]
+    fn test_get_entry_reports_tombstone() {
+        let mut sl = SkipList::new();
+        let tombstone = InternalKey::new(b"gone".to_vec(), 2, KeyType::Delete);
+        let live = make_key("empty", 1);
+
+        sl.insert(tombstone.clone(), Vec::new()).unwrap();
+        sl.insert(live.clone(), Vec::new()).unwrap();
+
+        assert_eq!(
+            sl.get_entry(&tombstone),
+            Some((KeyType::Delete, Vec::new()))
+        );
+        assert_eq!(sl.get_entry(&live), Some((KeyType::Put, Vec::new())));
+        assert_eq!(sl.get_entry(&make_key("gone", 2)), None);
+    }
+
+    #[test]
+    fn test_seek_lands_on_first_key_at_or_after() {
+        let mut sl = SkipList::new();
+        for key in ["a", "c", "e"] {
+            sl.insert(make_key(key, 1), key.as_bytes().to_vec())
+                .unwrap();
+        }
+
+        let from_c: Vec<_> = sl.seek(&make_key("c", 1)).map(|(_, v)| v).collect();
+        assert_eq!(from_c, vec![b"c".to_vec(), b"e".to_vec()]);
+
+        let from_d: Vec<_> = sl.seek(&make_key("d", 1)).map(|(_, v)| v).collect();
+        assert_eq!(from_d, vec![b"e".to_vec()]);
+
+        assert_eq!(sl.seek(&make_key("f", 1)).count(), 0);
+        assert_eq!(sl.seek(&make_key("", 1)).count(), 3);
+    }
+
+    #[test]
+    fn test_merge_from_keeps_higher_sequence_first() {
+        let mut older = SkipList::new();
+        older.insert(make_key("a", 1), b"a1".to_vec()).unwrap();
+        older.insert(make_key("b", 2), b"b2".to_vec()).unwrap();
+        older.insert(make_key("c", 3), b"c3".to_vec()).unwrap();
+
+        let mut newer = SkipList::new();
+        newer.insert(make_key("a", 4), b"a4".to_vec()).unwrap();
+        newer.insert(make_key("b", 5), b"b5".to_vec()).unwrap();
+        newer.insert(make_key("d", 6), b"d6".to_vec()).unwrap();
+
+        older.merge_from(newer).unwrap();
+
+        assert_eq!(older.len(), 6);
+        let latest = |user_key: &[u8]| {
+            older
+                .seek(&InternalKey::for_lookup(user_key, u64::MAX))
+                .next()
+                .map(|(_, v)| v)
+        };
+        assert_eq!(latest(b"a"), Some(b"a4".to_vec()));
+        assert_eq!(latest(b"b"), Some(b"b5".to_vec()));
+        assert_eq!(latest(b"c"), Some(b"c3".to_vec()));
+        assert_eq!(latest(b"d"), Some(b"d6".to_vec()));
+
+        // The older versions are still there for snapshots below the newer sequence
+        assert_eq!(older.get(&make_key("a", 1)), Some(b"a1".to_vec()));
+    }
+
+    #[test]
+    fn test_merge_from_exact_key_takes_other_value() {
+        let mut list = SkipList::new();
+        list.insert(make_key("a", 1), b"old".to_vec()).unwrap();
+
+        let mut other = SkipList::new();
+        other.insert(make_key("a", 1), b"new".to_vec()).unwrap();
+        other.insert(make_key("z", 2), b"z".to_vec()).unwrap();
+
+        list.merge_from(other).unwrap();
+
+        assert_eq!(list.len(), 2);
+        assert_eq!(list.get(&make_key("a", 1)), Some(b"new".to_vec()));
+    }
+
+    #[test]
+    fn test_merge_from_empty() {
+        let mut list = SkipList::new();
+        list.merge_from(SkipList::new()).unwrap();
+        assert!(list.is_empty());
+
+        let mut other = SkipList::new();
+        other.insert(make_key("a", 1), b"a".to_vec()).unwrap();
+        list.merge_from(other).unwrap();
+        assert_eq!(list.len(), 1);
+    }
+
     #[test]
     fn test_large_value() {
         let mut sl = SkipList::new();
@@ -555,4 +775,126 @@ mod tests {
         assert_eq!(items[1].0, make_key("m", 5));
         assert_eq!(items[2].0, make_key("z", 10));
     }
+
+    #[test]
+    fn test_contains_key_matches_get() {
+        let mut sl = SkipList::new();
+
+        sl.insert(make_key("a", 1), b"1".to_vec()).unwrap();
+        sl.insert(make_key("c", 3), Vec::new()).unwrap();
+        sl.insert(make_key("e", 5), b"5".to_vec()).unwrap();
+
+        for (k, seq) in [("a", 1), ("b", 2), ("c", 3), ("c", 4), ("e", 5), ("z", 9)] {
+            let key = make_key(k, seq);
+            assert_eq!(sl.contains_key(&key), sl.get(&key).is_some());
+        }
+
+        assert!(sl.delete(&make_key("a", 1)));
+        assert!(!sl.contains_key(&make_key("a", 1)));
+    }
+
+    #[test]
+    fn test_with_value_sees_stored_bytes() {
+        let mut sl = SkipList::new();
+        let key = make_key("key", 1);
+
+        sl.insert(key.clone(), b"value".to_vec()).unwrap();
+
+        assert_eq!(sl.with_value(&key, |v| v.to_vec()), Some(b"value".to_vec()));
+        assert_eq!(sl.with_value(&key, |v| v[..3] == *b"val"), Some(true));
+        assert_eq!(sl.with_value(&make_key("missing", 1), |v| v.len()), None);
+
+        sl.insert(key.clone(), b"updated".to_vec()).unwrap();
+        assert_eq!(sl.with_value(&key, |v| v.len()), Some(7));
+    }
+
+    #[test]
+    fn test_iterator_reads_alongside_lookups() {
+        let mut sl = SkipList::new();
+
+        sl.insert(make_key("a", 1), b"1".to_vec()).unwrap();
+        sl.insert(make_key("b", 2), b"2".to_vec()).unwrap();
+
+        // Shared reads are fine while iterating, mutation is rejected by the borrow checker
+        for (key, value) in sl.iter() {
+            assert_eq!(sl.get(&key), Some(value));
+            assert!(sl.contains_key(&key));
+        }
+    }
+
+    #[test]
+    fn test_mutation_after_iterator_dropped() {
+        let mut sl = SkipList::new();
+
+        sl.insert(make_key("a", 1), b"1".to_vec()).unwrap();
+        sl.insert(make_key("c", 3), b"3".to_vec()).unwrap();
+
+        let before: Vec<_> = sl.iter().collect();
+
+        sl.insert(make_key("b", 2), b"2".to_vec()).unwrap();
+        assert!(sl.delete(&make_key("a", 1)));
+
+        // The collected items are owned and unaffected by the later mutation
+        assert_eq!(before.len(), 2);
+        assert_eq!(before[0].0, make_key("a", 1));
+
+        let after: Vec<_> = sl.iter().map(|(k, _)| k).collect();
+        assert_eq!(after, vec![make_key("b", 2), make_key("c", 3)]);
+    }
+
+    #[test]
+    fn test_reverse_iterator_empty() {
+        let sl = SkipList::new();
+        assert_eq!(sl.iter_rev().count(), 0);
+    }
+
+    #[test]
+    fn test_reverse_iterator_is_exact_reverse() {
+        let mut sl = SkipList::new();
+
+        for (i, k) in ["m", "a", "z", "d", "p"].iter().enumerate() {
+            sl.insert(make_key(k, i as u64), k.as_bytes().to_vec())
+                .unwrap();
+        }
+        sl.insert(make_key("m", 10), b"newer".to_vec()).unwrap();
+
+        let forward: Vec<_> = sl.iter().collect();
+        let mut reverse: Vec<_> = sl.iter_rev().collect();
+
+        assert_eq!(reverse.len(), 6);
+        assert_eq!(reverse[0].0, make_key("z", 2));
+
+        reverse.reverse();
+        assert_eq!(forward, reverse);
+    }
+
+    #[test]
+    fn test_search_path_is_reused_and_released() {
+        let mut sl = SkipList::new();
+
+        for i in 0..200 {
+            sl.insert(make_key(&format!("key{:03}", i), i), b"v".to_vec())
+                .unwrap();
+        }
+
+        let buffer = sl.search_path.borrow().as_ptr();
+        for i in 0..200 {
+            assert!(sl.contains_key(&make_key(&format!("key{:03}", i), i)));
+        }
+        assert!(sl.delete(&make_key("key100", 100)));
+
+        // Same allocation as before, and no node pointers held between operations
+        assert_eq!(sl.search_path.borrow().as_ptr(), buffer);
+        assert!(sl.search_path.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_with_value_callback_can_reenter() {
+        let mut sl = SkipList::new();
+        sl.insert(make_key("a", 1), b"1".to_vec()).unwrap();
+        sl.insert(make_key("b", 2), b"2".to_vec()).unwrap();
+
+        let nested = sl.with_value(&make_key("a", 1), |_| sl.get(&make_key("b", 2)));
+        assert_eq!(nested, Some(Some(b"2".to_vec())));
+    }
 }
@@ -54,6 +54,8 @@ impl Ord for InternalKey {
             return Ordering::Greater;
         }
 
+        // Two records should never share a user key and sequence, but if they do the Delete sorts
+        // first, so a reader taking the first entry at or below its snapshot sees the tombstone.
         self.key_type.cmp(&other.key_type)
     }
 }
@@ -81,6 +83,17 @@ impl InternalKey {
         }
     }
 
+    // The smallest InternalKey for user_key visible at snapshot_seq. Sequences sort descending and
+    // Delete sorts before Put, so seeking to this key lands on the newest version <= snapshot_seq,
+    // and on the tombstone if a put and delete share that sequence.
+    pub fn for_lookup(user_key: &[u8], snapshot_seq: u64) -> Self {
+        InternalKey {
+            user_key: user_key.to_vec(),
+            sequence_number: snapshot_seq,
+            key_type: KeyType::Delete,
+        }
+    }
+
     pub fn is_deletion(&self) -> bool {
         matches!(self.key_type, KeyType::Delete)
     }
@@ -115,10 +128,89 @@ impl InternalKey {
     }
 
     pub fn encode(&self) -> Vec<u8> {
-        let mut result: Vec<u8> = Vec::with_capacity(self.user_key.len() + 9);
-        result.extend(&self.user_key);
-        result.extend_from_slice(&self.sequence_number.to_be_bytes());
-        result.push(u8::from(self.key_type));
+        let mut result: Vec<u8> = Vec::with_capacity(self.encoded_len());
+        self.encode_into(&mut result);
         result
     }
+
+    pub fn encode_into(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.user_key);
+        out.extend_from_slice(&self.sequence_number.to_be_bytes());
+        out.push(u8::from(self.key_type));
+    }
+
+    pub fn encoded_len(&self) -> usize {
+        self.user_key.len() + 9
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    // Locks the on-disk layout: user key, big-endian sequence, then the type byte
+    #[test]
+    fn test_encode_canonical_bytes() {
+        let key = InternalKey::new(b"key".to_vec(), 0x0102_0304_0506_0708, KeyType::Put);
+
+        assert_eq!(
+            key.encode(),
+            vec![
+                b'k', b'e', b'y', 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x01
+            ]
+        );
+
+        let tombstone = InternalKey::new(Vec::new(), 1, KeyType::Delete);
+        assert_eq!(tombstone.encode(), vec![0, 0, 0, 0, 0, 0, 0, 1, 0]);
+    }
+
+    #[test]
+    fn test_decode_canonical_bytes() {
+        let bytes = [b'a', 0, 0, 0, 0, 0, 0, 0x01, 0x00, 0x01];
+        let key = InternalKey::decode(&bytes).unwrap();
+
+        assert_eq!(key.user_key, b"a".to_vec());
+        assert_eq!(key.sequence_number, 256);
+        assert_eq!(key.key_type, KeyType::Put);
+    }
+
+    #[test]
+    fn test_random_round_trip() {
+        let mut rng = rand::rng();
+
+        for _ in 0..1000 {
+            let len = rng.random_range(0..64);
+            let user_key: Vec<u8> = (0..len).map(|_| rng.random()).collect();
+            let key_type = if rng.random() {
+                KeyType::Put
+            } else {
+                KeyType::Delete
+            };
+            let key = InternalKey::new(user_key, rng.random(), key_type);
+
+            let decoded = InternalKey::decode(&key.encode()).unwrap();
+            assert_eq!(decoded, key);
+        }
+    }
+
+    #[test]
+    fn test_equal_sequence_delete_sorts_before_put() {
+        let delete = InternalKey::new(b"key".to_vec(), 5, KeyType::Delete);
+        let put = InternalKey::new(b"key".to_vec(), 5, KeyType::Put);
+
+        assert_eq!(delete.cmp(&put), Ordering::Less);
+        assert_eq!(put.cmp(&delete), Ordering::Greater);
+        assert_ne!(delete, put);
+
+        // Sequence still dominates key type
+        let older_delete = InternalKey::new(b"key".to_vec(), 4, KeyType::Delete);
+        assert_eq!(put.cmp(&older_delete), Ordering::Less);
+    }
+
+    #[test]
+    fn test_decode_rejects_short_or_unknown_type() {
+        assert!(InternalKey::decode(&[0; 8]).is_err());
+        assert!(InternalKey::decode(&[0, 0, 0, 0, 0, 0, 0, 1, 2]).is_err());
+    }
 }
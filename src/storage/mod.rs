@@ -1 +1,3 @@
 pub mod internal_key;
+pub mod ordered_key;
+pub mod sequence;
@@ -0,0 +1,151 @@
+// Encodings whose bytewise order matches the logical order of the value, so structured keys
+// sort correctly under the default comparator.
+pub trait OrderedKey {
+    fn encode_into(&self, out: &mut Vec<u8>);
+
+    fn encode_ordered(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode_into(&mut out);
+        out
+    }
+}
+
+impl OrderedKey for u64 {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_be_bytes());
+    }
+}
+
+impl OrderedKey for u32 {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_be_bytes());
+    }
+}
+
+impl OrderedKey for i64 {
+    // Flipping the sign bit moves negatives below positives
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&((*self as u64) ^ (1 << 63)).to_be_bytes());
+    }
+}
+
+// A plain length prefix would sort "b" before "aa", so variable-length bytes are escaped instead:
+// every 0x00 becomes 0x00 0xFF and the value ends with 0x00 0x01. The terminator sorts below any
+// escaped byte, so a prefix always sorts before its extensions.
+impl OrderedKey for [u8] {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        for &b in self {
+            out.push(b);
+            if b == 0 {
+                out.push(0xFF);
+            }
+        }
+        out.extend_from_slice(&[0x00, 0x01]);
+    }
+}
+
+impl OrderedKey for Vec<u8> {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        self.as_slice().encode_into(out);
+    }
+}
+
+impl OrderedKey for str {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        self.as_bytes().encode_into(out);
+    }
+}
+
+impl OrderedKey for String {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        self.as_bytes().encode_into(out);
+    }
+}
+
+impl<T: OrderedKey + ?Sized> OrderedKey for &T {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        (**self).encode_into(out);
+    }
+}
+
+impl<A: OrderedKey, B: OrderedKey> OrderedKey for (A, B) {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        self.0.encode_into(out);
+        self.1.encode_into(out);
+    }
+}
+
+impl<A: OrderedKey, B: OrderedKey, C: OrderedKey> OrderedKey for (A, B, C) {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        self.0.encode_into(out);
+        self.1.encode_into(out);
+        self.2.encode_into(out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    fn assert_order_preserved<K: OrderedKey + Ord + Clone + std::fmt::Debug>(mut keys: Vec<K>) {
+        let mut encoded: Vec<(Vec<u8>, K)> = keys
+            .iter()
+            .map(|k| (k.encode_ordered(), k.clone()))
+            .collect();
+
+        keys.sort();
+        encoded.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let by_bytes: Vec<K> = encoded.into_iter().map(|(_, k)| k).collect();
+        assert_eq!(by_bytes, keys);
+    }
+
+    #[test]
+    fn test_u64_sorts_numerically() {
+        let mut rng = rand::rng();
+        let mut keys: Vec<u64> = (0..1000).map(|_| rng.random()).collect();
+        keys.extend([0, 1, 255, 256, u64::MAX]);
+
+        assert_order_preserved(keys);
+    }
+
+    #[test]
+    fn test_i64_sorts_numerically() {
+        let mut rng = rand::rng();
+        let mut keys: Vec<i64> = (0..1000).map(|_| rng.random()).collect();
+        keys.extend([i64::MIN, -1, 0, 1, i64::MAX]);
+
+        assert_order_preserved(keys);
+    }
+
+    #[test]
+    fn test_bytes_sort_lexicographically() {
+        let keys: Vec<Vec<u8>> = vec![
+            b"".to_vec(),
+            b"a".to_vec(),
+            b"a\0".to_vec(),
+            b"a\0\0".to_vec(),
+            b"a\x01".to_vec(),
+            b"aa".to_vec(),
+            b"b".to_vec(),
+            vec![0xFF],
+            vec![0x00],
+        ];
+
+        assert_order_preserved(keys);
+    }
+
+    #[test]
+    fn test_tuples_sort_component_wise() {
+        let keys: Vec<(String, u64)> = vec![
+            ("b".to_string(), 1),
+            ("aa".to_string(), 7),
+            ("a".to_string(), 300),
+            ("a".to_string(), 2),
+            ("".to_string(), u64::MAX),
+        ];
+
+        assert_order_preserved(keys);
+    }
+}
@@ -0,0 +1,74 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::errors::storage_errors::StorageError;
+
+// u64::MAX is kept free so it can be used as a "latest" snapshot when building lookup keys
+pub const MAX_SEQUENCE_NUMBER: u64 = u64::MAX - 1;
+
+#[derive(Debug)]
+pub struct SequenceAllocator {
+    last_sequence: AtomicU64,
+}
+
+impl SequenceAllocator {
+    pub fn new(last_sequence: u64) -> Self {
+        SequenceAllocator {
+            last_sequence: AtomicU64::new(last_sequence),
+        }
+    }
+
+    // Never wraps: once MAX_SEQUENCE_NUMBER has been handed out every further call errors, since
+    // a wrapped sequence would sort older than everything already written
+    pub fn next(&self) -> Result<u64, StorageError> {
+        self.last_sequence
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |last| {
+                (last < MAX_SEQUENCE_NUMBER).then_some(last + 1)
+            })
+            .map(|last| last + 1)
+            .map_err(StorageError::SequenceExhausted)
+    }
+
+    pub fn last_sequence(&self) -> u64 {
+        self.last_sequence.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allocation_is_monotonic() {
+        let allocator = SequenceAllocator::new(0);
+
+        assert_eq!(allocator.next().unwrap(), 1);
+        assert_eq!(allocator.next().unwrap(), 2);
+        assert_eq!(allocator.next().unwrap(), 3);
+        assert_eq!(allocator.last_sequence(), 3);
+    }
+
+    #[test]
+    fn test_allocation_continues_after_reopen() {
+        let allocator = SequenceAllocator::new(0);
+        for _ in 0..10 {
+            allocator.next().unwrap();
+        }
+        let persisted = allocator.last_sequence();
+
+        let reopened = SequenceAllocator::new(persisted);
+        assert_eq!(reopened.next().unwrap(), 11);
+    }
+
+    #[test]
+    fn test_exhaustion_is_reported() {
+        let allocator = SequenceAllocator::new(MAX_SEQUENCE_NUMBER - 2);
+
+        assert_eq!(allocator.next().unwrap(), MAX_SEQUENCE_NUMBER - 1);
+        assert_eq!(allocator.next().unwrap(), MAX_SEQUENCE_NUMBER);
+        assert!(matches!(
+            allocator.next(),
+            Err(StorageError::SequenceExhausted(MAX_SEQUENCE_NUMBER))
+        ));
+        assert_eq!(allocator.last_sequence(), MAX_SEQUENCE_NUMBER);
+    }
+}
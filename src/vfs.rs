@@ -0,0 +1,572 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    fs::{self, File, OpenOptions, TryLockError},
+    io::{BufReader, Error, ErrorKind, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex, MutexGuard,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+pub trait WritableFile: Write + Debug + Send {
+    fn sync(&mut self) -> Result<(), Error>;
+    fn set_len(&mut self, len: u64) -> Result<(), Error>;
+    // Moves the write position, has no effect on files opened with append
+    fn set_position(&mut self, pos: u64) -> Result<(), Error>;
+}
+
+// Every filesystem touch made by the FileManager and the WAL goes through this trait so the engine
+// can run against InMemoryFs in tests.
+pub trait FileSystem: Debug + Send + Sync {
+    fn exists(&self, path: &Path) -> bool;
+    fn is_file(&self, path: &Path) -> bool;
+    fn is_dir(&self, path: &Path) -> bool;
+    fn create_dir_all(&self, path: &Path) -> Result<(), Error>;
+    fn read_dir(&self, path: &Path) -> Result<Vec<PathBuf>, Error>;
+    // Fails with AlreadyExists if the file is present
+    fn create_new(&self, path: &Path) -> Result<Box<dyn WritableFile>, Error>;
+    // Creates or truncates
+    fn create(&self, path: &Path) -> Result<Box<dyn WritableFile>, Error>;
+    // Creates if missing and positions writes at the end of the file
+    fn append(&self, path: &Path) -> Result<Box<dyn WritableFile>, Error>;
+    // Creates if missing without truncating, writes start at the beginning of the file
+    fn open_write(&self, path: &Path) -> Result<Box<dyn WritableFile>, Error>;
+    fn read(&self, path: &Path) -> Result<Vec<u8>, Error>;
+    // Streams the file instead of loading it, for callers that must bound their memory
+    fn open_read(&self, path: &Path) -> Result<Box<dyn Read + Send>, Error>;
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), Error>;
+    fn remove_file(&self, path: &Path) -> Result<(), Error>;
+    fn sync_dir(&self, path: &Path) -> Result<(), Error>;
+
+    // Creates if missing and takes an exclusive OS advisory lock that lasts until the returned
+    // file is dropped, or the process dies. Fails with WouldBlock if another handle holds it.
+    fn lock_file(&self, _path: &Path) -> Result<Box<dyn WritableFile>, Error> {
+        Err(Error::new(
+            ErrorKind::Unsupported,
+            "advisory locking is not supported by this file system",
+        ))
+    }
+
+    fn read_to_string(&self, path: &Path) -> Result<String, Error> {
+        String::from_utf8(self.read(path)?).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+    }
+}
+
+// Retries op while it fails with ErrorKind::Interrupted (EINTR). write_all and read_to_end already
+// do this, but one-shot calls like sync_all, set_len and open do not.
+pub fn retry_interrupted<T, F>(mut op: F) -> Result<T, Error>
+where
+    F: FnMut() -> Result<T, Error>,
+{
+    loop {
+        match op() {
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            result => return result,
+        }
+    }
+}
+
+// ===========================================
+// |        Std FileSystem                   |
+// ===========================================
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StdFs;
+
+impl WritableFile for File {
+    fn sync(&mut self) -> Result<(), Error> {
+        retry_interrupted(|| self.sync_all())
+    }
+
+    fn set_len(&mut self, len: u64) -> Result<(), Error> {
+        retry_interrupted(|| File::set_len(self, len))
+    }
+
+    fn set_position(&mut self, pos: u64) -> Result<(), Error> {
+        retry_interrupted(|| self.seek(SeekFrom::Start(pos))).map(|_| ())
+    }
+}
+
+impl FileSystem for StdFs {
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+
+    fn create_dir_all(&self, path: &Path) -> Result<(), Error> {
+        fs::create_dir_all(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<PathBuf>, Error> {
+        fs::read_dir(path)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect()
+    }
+
+    fn create_new(&self, path: &Path) -> Result<Box<dyn WritableFile>, Error> {
+        let file =
+            retry_interrupted(|| OpenOptions::new().write(true).create_new(true).open(path))?;
+        Ok(Box::new(file))
+    }
+
+    fn create(&self, path: &Path) -> Result<Box<dyn WritableFile>, Error> {
+        let file = retry_interrupted(|| {
+            OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(path)
+        })?;
+        Ok(Box::new(file))
+    }
+
+    fn append(&self, path: &Path) -> Result<Box<dyn WritableFile>, Error> {
+        let file = retry_interrupted(|| OpenOptions::new().append(true).create(true).open(path))?;
+        Ok(Box::new(file))
+    }
+
+    fn open_write(&self, path: &Path) -> Result<Box<dyn WritableFile>, Error> {
+        let file = retry_interrupted(|| {
+            OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(path)
+        })?;
+        Ok(Box::new(file))
+    }
+
+    fn read(&self, path: &Path) -> Result<Vec<u8>, Error> {
+        retry_interrupted(|| fs::read(path))
+    }
+
+    // BufReader, like read_to_end, retries Interrupted reads
+    fn open_read(&self, path: &Path) -> Result<Box<dyn Read + Send>, Error> {
+        let file = retry_interrupted(|| File::open(path))?;
+        Ok(Box::new(BufReader::new(file)))
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        fs::rename(from, to)
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<(), Error> {
+        fs::remove_file(path)
+    }
+
+    #[cfg(unix)]
+    fn sync_dir(&self, path: &Path) -> Result<(), Error> {
+        let dir = retry_interrupted(|| File::open(path))?;
+        retry_interrupted(|| dir.sync_all())
+    }
+
+    // Directories can't be opened as files on other platforms, renames there are already durable
+    #[cfg(not(unix))]
+    fn sync_dir(&self, _path: &Path) -> Result<(), Error> {
+        Ok(())
+    }
+
+    // flock on Unix, LockFileEx on Windows
+    fn lock_file(&self, path: &Path) -> Result<Box<dyn WritableFile>, Error> {
+        let file = retry_interrupted(|| {
+            OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(path)
+        })?;
+        file.try_lock().map_err(|e| match e {
+            TryLockError::WouldBlock => Error::new(ErrorKind::WouldBlock, "file is locked"),
+            TryLockError::Error(e) => e,
+        })?;
+        Ok(Box::new(file))
+    }
+}
+
+// ===========================================
+// |        In Memory FileSystem             |
+// ===========================================
+
+type SharedBytes = Arc<Mutex<Vec<u8>>>;
+
+#[derive(Debug, Default)]
+struct InMemoryState {
+    files: HashMap<PathBuf, SharedBytes>,
+    dirs: HashSet<PathBuf>,
+}
+
+// Clones share the same underlying state, so a test can hand one clone to the engine and keep
+// another to inspect the files it wrote.
+#[derive(Debug, Default, Clone)]
+pub struct InMemoryFs {
+    state: Arc<Mutex<InMemoryState>>,
+    // Total bytes handed out by read and open_read, so tests can check how much was loaded
+    bytes_read: Arc<AtomicU64>,
+}
+
+struct InMemoryReader {
+    data: SharedBytes,
+    pos: usize,
+    bytes_read: Arc<AtomicU64>,
+}
+
+impl Read for InMemoryReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let data = lock(&self.data)?;
+        let n = data.len().saturating_sub(self.pos).min(buf.len());
+        buf[..n].copy_from_slice(&data[self.pos..self.pos + n]);
+        self.pos += n;
+        self.bytes_read.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+}
+
+#[derive(Debug)]
+struct InMemoryFile {
+    data: SharedBytes,
+    pos: usize,
+    append: bool,
+}
+
+impl InMemoryFile {
+    fn new(data: SharedBytes, append: bool) -> Box<Self> {
+        Box::new(InMemoryFile {
+            data,
+            pos: 0,
+            append,
+        })
+    }
+}
+
+impl Write for InMemoryFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut data = lock(&self.data)?;
+        if self.append {
+            self.pos = data.len();
+        }
+
+        let end = self.pos + buf.len();
+        if end > data.len() {
+            data.resize(end, 0);
+        }
+        data[self.pos..end].copy_from_slice(buf);
+        self.pos = end;
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl WritableFile for InMemoryFile {
+    fn sync(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn set_len(&mut self, len: u64) -> Result<(), Error> {
+        lock(&self.data)?.resize(len as usize, 0);
+        Ok(())
+    }
+
+    fn set_position(&mut self, pos: u64) -> Result<(), Error> {
+        self.pos = pos as usize;
+        Ok(())
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> Result<MutexGuard<'_, T>, Error> {
+    mutex
+        .lock()
+        .map_err(|_| Error::other("in memory fs lock poisoned"))
+}
+
+fn not_found(path: &Path) -> Error {
+    Error::new(ErrorKind::NotFound, format!("{} not found", path.display()))
+}
+
+impl InMemoryFs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read.load(Ordering::Relaxed)
+    }
+
+    fn check_parent(state: &InMemoryState, path: &Path) -> Result<(), Error> {
+        match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() && !state.dirs.contains(parent) => {
+                Err(not_found(parent))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl FileSystem for InMemoryFs {
+    fn exists(&self, path: &Path) -> bool {
+        self.is_file(path) || self.is_dir(path)
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        lock(&self.state)
+            .map(|s| s.files.contains_key(path))
+            .unwrap_or(false)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        lock(&self.state)
+            .map(|s| s.dirs.contains(path))
+            .unwrap_or(false)
+    }
+
+    fn create_dir_all(&self, path: &Path) -> Result<(), Error> {
+        let mut state = lock(&self.state)?;
+        if state.files.contains_key(path) {
+            return Err(Error::new(
+                ErrorKind::AlreadyExists,
+                format!("{} is a file", path.display()),
+            ));
+        }
+        for ancestor in path.ancestors() {
+            if !ancestor.as_os_str().is_empty() {
+                state.dirs.insert(ancestor.to_path_buf());
+            }
+        }
+        Ok(())
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<PathBuf>, Error> {
+        let state = lock(&self.state)?;
+        if !state.dirs.contains(path) {
+            return Err(not_found(path));
+        }
+        let entries = state
+            .files
+            .keys()
+            .chain(state.dirs.iter())
+            .filter(|p| p.parent() == Some(path))
+            .cloned()
+            .collect();
+        Ok(entries)
+    }
+
+    fn create_new(&self, path: &Path) -> Result<Box<dyn WritableFile>, Error> {
+        let mut state = lock(&self.state)?;
+        Self::check_parent(&state, path)?;
+        if state.files.contains_key(path) || state.dirs.contains(path) {
+            return Err(Error::new(
+                ErrorKind::AlreadyExists,
+                format!("{} already exists", path.display()),
+            ));
+        }
+        let data = SharedBytes::default();
+        state.files.insert(path.to_path_buf(), Arc::clone(&data));
+        Ok(InMemoryFile::new(data, false))
+    }
+
+    fn create(&self, path: &Path) -> Result<Box<dyn WritableFile>, Error> {
+        let mut state = lock(&self.state)?;
+        Self::check_parent(&state, path)?;
+        let data = SharedBytes::default();
+        state.files.insert(path.to_path_buf(), Arc::clone(&data));
+        Ok(InMemoryFile::new(data, false))
+    }
+
+    fn append(&self, path: &Path) -> Result<Box<dyn WritableFile>, Error> {
+        let mut state = lock(&self.state)?;
+        Self::check_parent(&state, path)?;
+        let data = state.files.entry(path.to_path_buf()).or_default();
+        Ok(InMemoryFile::new(Arc::clone(data), true))
+    }
+
+    fn open_write(&self, path: &Path) -> Result<Box<dyn WritableFile>, Error> {
+        let mut state = lock(&self.state)?;
+        Self::check_parent(&state, path)?;
+        let data = state.files.entry(path.to_path_buf()).or_default();
+        Ok(InMemoryFile::new(Arc::clone(data), false))
+    }
+
+    fn read(&self, path: &Path) -> Result<Vec<u8>, Error> {
+        let state = lock(&self.state)?;
+        let data = state.files.get(path).ok_or_else(|| not_found(path))?;
+        let data = lock(data)?.clone();
+        self.bytes_read
+            .fetch_add(data.len() as u64, Ordering::Relaxed);
+        Ok(data)
+    }
+
+    fn open_read(&self, path: &Path) -> Result<Box<dyn Read + Send>, Error> {
+        let state = lock(&self.state)?;
+        let data = state.files.get(path).ok_or_else(|| not_found(path))?;
+        Ok(Box::new(InMemoryReader {
+            data: Arc::clone(data),
+            pos: 0,
+            bytes_read: Arc::clone(&self.bytes_read),
+        }))
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        let mut state = lock(&self.state)?;
+        Self::check_parent(&state, to)?;
+        let data = state.files.remove(from).ok_or_else(|| not_found(from))?;
+        state.files.insert(to.to_path_buf(), data);
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<(), Error> {
+        let mut state = lock(&self.state)?;
+        state
+            .files
+            .remove(path)
+            .map(|_| ())
+            .ok_or_else(|| not_found(path))
+    }
+
+    fn sync_dir(&self, path: &Path) -> Result<(), Error> {
+        if !self.is_dir(path) {
+            return Err(not_found(path));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    // Fails each call with Interrupted `interrupts` times before succeeding
+    struct InterruptingReader {
+        interrupts: usize,
+        data: &'static [u8],
+    }
+
+    impl Read for InterruptingReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.interrupts > 0 {
+                self.interrupts -= 1;
+                return Err(Error::from(ErrorKind::Interrupted));
+            }
+            let n = self.data.len().min(buf.len());
+            buf[..n].copy_from_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_retry_interrupted_completes_after_eintr() {
+        let mut reader = InterruptingReader {
+            interrupts: 1,
+            data: b"CURRENT",
+        };
+        let mut buf = [0u8; 16];
+
+        let n = retry_interrupted(|| reader.read(&mut buf)).unwrap();
+
+        assert_eq!(&buf[..n], b"CURRENT");
+        assert_eq!(reader.interrupts, 0);
+    }
+
+    #[test]
+    fn test_retry_interrupted_passes_other_errors_through() {
+        let mut calls = 0;
+        let result: Result<(), Error> = retry_interrupted(|| {
+            calls += 1;
+            Err(Error::from(ErrorKind::NotFound))
+        });
+
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn test_in_memory_create_write_read() {
+        let fs = InMemoryFs::new();
+        let dir = Path::new("/db");
+        fs.create_dir_all(dir).unwrap();
+
+        let path = dir.join("CURRENT");
+        let mut file = fs.create_new(&path).unwrap();
+        file.write_all(b"MANIFEST-000001\n").unwrap();
+        file.sync().unwrap();
+
+        assert!(fs.is_file(&path));
+        assert!(fs.is_dir(dir));
+        assert_eq!(fs.read_to_string(&path).unwrap(), "MANIFEST-000001\n");
+        assert_eq!(fs.read_dir(dir).unwrap(), vec![path.clone()]);
+
+        let err = fs.create_new(&path).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+    }
+
+    #[test]
+    fn test_in_memory_append_create_and_rename() {
+        let fs = InMemoryFs::new();
+        let dir = Path::new("/db");
+        fs.create_dir_all(dir).unwrap();
+
+        let log = dir.join("000002.log");
+        fs.append(&log).unwrap().write_all(b"ab").unwrap();
+        fs.append(&log).unwrap().write_all(b"cd").unwrap();
+        assert_eq!(fs.read(&log).unwrap(), b"abcd");
+
+        fs.create(&log).unwrap().write_all(b"x").unwrap();
+        assert_eq!(fs.read(&log).unwrap(), b"x");
+
+        let moved = dir.join("000003.log");
+        fs.rename(&log, &moved).unwrap();
+        assert!(!fs.exists(&log));
+        assert_eq!(fs.read(&moved).unwrap(), b"x");
+
+        fs.remove_file(&moved).unwrap();
+        assert_eq!(
+            fs.remove_file(&moved).unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn test_in_memory_requires_parent_dir() {
+        let fs = InMemoryFs::new();
+
+        let err = fs.create_new(Path::new("/missing/LOCK")).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert_eq!(
+            fs.read_dir(Path::new("/missing")).unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn test_in_memory_positioned_writes() {
+        let fs = InMemoryFs::new();
+        let dir = Path::new("/db");
+        fs.create_dir_all(dir).unwrap();
+
+        let path = dir.join("000002.log");
+        let mut file = fs.open_write(&path).unwrap();
+        file.set_len(8).unwrap();
+        file.write_all(b"ab").unwrap();
+        assert_eq!(fs.read(&path).unwrap(), b"ab\0\0\0\0\0\0");
+
+        file.set_position(6).unwrap();
+        file.write_all(b"yz12").unwrap();
+        assert_eq!(fs.read(&path).unwrap(), b"ab\0\0\0\0yz12");
+
+        file.set_len(2).unwrap();
+        assert_eq!(fs.read(&path).unwrap(), b"ab");
+    }
+}
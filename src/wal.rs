@@ -1,21 +1,49 @@
 use std::{
-    fs::{File, OpenOptions},
-    io::{Error, Write},
-    path::PathBuf,
+    io::{Error, ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Instant,
 };
 
 use crc32fast::Hasher;
 
 use crate::{
-    errors::storage_errors::StorageError, file_manager, storage::internal_key::InternalKey,
+    config::tconfig::TaurusConfig,
+    errors::storage_errors::StorageError,
+    metrics::Metrics,
+    storage::internal_key::{InternalKey, KeyType},
+    vfs::{FileSystem, StdFs, WritableFile, retry_interrupted},
 };
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 enum EntryType {
     Put,
     Delete,
 }
 
+impl TryFrom<u8> for EntryType {
+    type Error = StorageError;
+
+    fn try_from(value: u8) -> Result<Self, StorageError> {
+        match value {
+            0 => Ok(EntryType::Put),
+            1 => Ok(EntryType::Delete),
+            _ => Err(StorageError::DecodeError(format!(
+                "unknown WAL entry type {}",
+                value
+            ))),
+        }
+    }
+}
+
+// Whether a record is a put or a delete is decided only by the entry type byte, never by the
+// value length, so a put of an empty value replays as a live key.
+#[derive(Debug, PartialEq)]
+pub enum WalRecord {
+    Put(InternalKey, Vec<u8>),
+    Delete(InternalKey),
+}
+
 impl From<EntryType> for u8 {
     fn from(value: EntryType) -> Self {
         match value {
@@ -25,80 +53,1111 @@ impl From<EntryType> for u8 {
     }
 }
 
+// Largest write buffer kept between records
+const MAX_RETAINED_ENTRY_BUFFER: usize = 1024 * 1024;
+
+fn checked_len(len: usize) -> Result<u32, Error> {
+    u32::try_from(len)
+        .map_err(|_| Error::new(ErrorKind::InvalidInput, StorageError::LengthOverflow(len)))
+}
+
 #[derive(Debug)]
 pub struct WriteAheadLog {
-    file: File,
+    file: Box<dyn WritableFile>,
     path: PathBuf,
     bytes_written: u64,
+    // Only tracked for preallocated logs, where the file length no longer marks the last record
+    logical_end: Option<u64>,
+    max_key_size: usize,
+    max_value_size: usize,
+    metrics: Option<Arc<Metrics>>,
+    entry_buf: Vec<u8>,
 }
 
 impl WriteAheadLog {
     pub fn new(path: PathBuf) -> Result<Self, Error> {
-        let file = OpenOptions::new().append(true).create(true).open(&path)?;
+        Self::new_with_config(path, &TaurusConfig::default())
+    }
+
+    pub fn new_with_config(path: PathBuf, config: &TaurusConfig) -> Result<Self, Error> {
+        Self::new_with_limits(
+            path,
+            config.max_key_size() as usize,
+            config.max_value_size() as usize,
+        )
+    }
+
+    pub fn new_with_limits(
+        path: PathBuf,
+        max_key_size: usize,
+        max_value_size: usize,
+    ) -> Result<Self, Error> {
+        Self::open(Arc::new(StdFs), path, max_key_size, max_value_size)
+    }
+
+    pub fn open(
+        fs: Arc<dyn FileSystem>,
+        path: PathBuf,
+        max_key_size: usize,
+        max_value_size: usize,
+    ) -> Result<Self, Error> {
+        let file = fs.append(&path)?;
         Ok(WriteAheadLog {
             file,
             path,
             bytes_written: 0,
+            logical_end: None,
+            max_key_size,
+            max_value_size,
+            metrics: None,
+            entry_buf: Vec::new(),
         })
     }
 
-    pub fn write_put(&mut self, key: &InternalKey, value: &[u8]) -> Result<(), Error> {
-        let k_bytes = key.encode();
-        let k_len = k_bytes.len() as u32;
-        let v_len = value.len() as u32;
+    // Grows the file to preallocate_size up front (set_len, sparse on most filesystems) so appends
+    // don't extend it a record at a time. Writes continue after the last valid record and close
+    // trims the zeroed tail. Fails with InvalidData if the existing log is corrupt.
+    pub fn open_preallocated(
+        fs: Arc<dyn FileSystem>,
+        path: PathBuf,
+        max_key_size: usize,
+        max_value_size: usize,
+        preallocate_size: u64,
+    ) -> Result<Self, Error> {
+        let existing = if fs.exists(&path) {
+            fs.read(&path)?
+        } else {
+            Vec::new()
+        };
+        // A corrupt log has to go through repair first, otherwise new records would overwrite
+        // the valid ones after the corruption
+        let logical_end =
+            logical_len(&existing).map_err(|e| Error::new(ErrorKind::InvalidData, e))? as u64;
 
-        let mut hasher = Hasher::new();
-        hasher.update(&[0u8]);
-        hasher.update(&k_len.to_be_bytes());
-        hasher.update(&k_bytes);
-        hasher.update(&v_len.to_be_bytes());
-        hasher.update(value);
-        let crc = hasher.finalize();
+        let mut file = fs.open_write(&path)?;
+        if preallocate_size > existing.len() as u64 {
+            file.set_len(preallocate_size)?;
+            file.sync()?;
+        }
+        file.set_position(logical_end)?;
 
-        let mut entry_bytes = Vec::new();
+        Ok(WriteAheadLog {
+            file,
+            path,
+            bytes_written: 0,
+            logical_end: Some(logical_end),
+            max_key_size,
+            max_value_size,
+            metrics: None,
+            entry_buf: Vec::new(),
+        })
+    }
 
-        entry_bytes.push(0u8);
-        entry_bytes.extend_from_slice(&k_len.to_be_bytes());
-        entry_bytes.extend_from_slice(&k_bytes);
-        entry_bytes.extend_from_slice(&v_len.to_be_bytes());
-        entry_bytes.extend_from_slice(value);
-        entry_bytes.extend_from_slice(&crc.to_be_bytes());
+    pub fn close(mut self) -> Result<(), Error> {
+        if let Some(end) = self.logical_end {
+            self.file.set_len(end)?;
+        }
+        self.file.sync()
+    }
 
-        self.file.write_all(&entry_bytes)?;
+    pub fn set_metrics(&mut self, metrics: Arc<Metrics>) {
+        self.metrics = Some(metrics);
+    }
+
+    fn append_entry(&mut self, entry_bytes: &[u8]) -> Result<(), Error> {
+        self.file.write_all(entry_bytes)?;
 
-        self.file.sync_all()?;
+        let sync_start = Instant::now();
+        self.file.sync()?;
+
+        if let Some(metrics) = &self.metrics {
+            metrics.increment("wal.records", 1);
+            metrics.increment("wal.bytes_written", entry_bytes.len() as u64);
+            metrics.record(
+                "wal.sync_latency_us",
+                sync_start.elapsed().as_micros() as u64,
+            );
+        }
 
         self.bytes_written += entry_bytes.len() as u64;
+        if let Some(end) = self.logical_end.as_mut() {
+            *end += entry_bytes.len() as u64;
+        }
 
         Ok(())
     }
 
+    fn check_sizes(&self, key: &InternalKey, value: &[u8]) -> Result<(), Error> {
+        if key.user_key.len() > self.max_key_size {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                StorageError::KeyTooLarge(key.user_key.len(), self.max_key_size),
+            ));
+        }
+
+        if value.len() > self.max_value_size {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                StorageError::ValueTooLarge(value.len(), self.max_value_size),
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn write_put(&mut self, key: &InternalKey, value: &[u8]) -> Result<(), Error> {
+        check_key_type(EntryType::Put, key)?;
+        self.check_sizes(key, value)?;
+        self.write_entry(EntryType::Put, key, value)
+    }
+
     pub fn write_delete(&mut self, key: &InternalKey) -> Result<(), Error> {
-        let k_bytes = key.encode();
-        let k_len = k_bytes.len() as u32;
-        let v_len: u32 = 0;
+        check_key_type(EntryType::Delete, key)?;
+        self.check_sizes(key, &[])?;
+        self.write_entry(EntryType::Delete, key, &[])
+    }
 
-        let mut hasher = Hasher::new();
-        hasher.update(&[1u8]);
-        hasher.update(&k_len.to_be_bytes());
-        hasher.update(&k_bytes);
-        hasher.update(&v_len.to_be_bytes());
-        let crc = hasher.finalize();
+    // Records are assembled in a buffer owned by the log so steady-state writes don't allocate.
+    // append_entry finishes with the bytes before the buffer is handed back for the next record.
+    fn write_entry(
+        &mut self,
+        entry_type: EntryType,
+        key: &InternalKey,
+        value: &[u8],
+    ) -> Result<(), Error> {
+        let k_len = checked_len(key.encoded_len())?;
+        let v_len = checked_len(value.len())?;
 
-        let mut entry_bytes = Vec::new();
+        let mut entry_bytes = std::mem::take(&mut self.entry_buf);
+        entry_bytes.clear();
 
-        entry_bytes.push(1u8);
+        entry_bytes.push(u8::from(entry_type));
         entry_bytes.extend_from_slice(&k_len.to_be_bytes());
-        entry_bytes.extend_from_slice(&k_bytes);
+        key.encode_into(&mut entry_bytes);
         entry_bytes.extend_from_slice(&v_len.to_be_bytes());
+        entry_bytes.extend_from_slice(value);
+
+        let crc = crc32fast::hash(&entry_bytes);
         entry_bytes.extend_from_slice(&crc.to_be_bytes());
 
-        self.file.write_all(&entry_bytes)?;
+        let result = self.append_entry(&entry_bytes);
 
-        self.file.sync_all()?;
+        // Don't hold on to the memory from one unusually large value
+        if entry_bytes.capacity() <= MAX_RETAINED_ENTRY_BUFFER {
+            self.entry_buf = entry_bytes;
+        }
 
-        self.bytes_written += entry_bytes.len() as u64;
+        result
+    }
 
-        Ok(())
+    pub fn replay(fs: &dyn FileSystem, path: &Path) -> Result<Vec<WalRecord>, Error> {
+        let data = fs.read(path)?;
+        let mut records = Vec::new();
+        let mut offset = 0;
+
+        while offset < data.len() {
+            match decode_record(&data[offset..]) {
+                Ok((record, len)) => {
+                    records.push(record);
+                    offset += len;
+                }
+                // The zeroed tail of a preallocated log is the logical end, not corruption
+                Err(_) if is_zero_tail(&data[offset..]) => break,
+                Err(e) => {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        e.at_offset(offset as u64),
+                    ));
+                }
+            }
+        }
+
+        Ok(records)
+    }
+
+    // Stops with FileTooLarge once the replayed records would pass max_replay_size bytes, so an
+    // oversized log (e.g. flushes never ran) fails fast instead of hanging recovery. The log is
+    // streamed a record at a time and lengths are checked before the bytes behind them are read,
+    // so no more than max_replay_size is ever loaded. A length the writer could never have
+    // produced under max_key_size/max_value_size is corruption, not an oversized log. force
+    // ignores the limit.
+    pub fn replay_with_limit(
+        fs: &dyn FileSystem,
+        path: &Path,
+        max_key_size: usize,
+        max_value_size: usize,
+        max_replay_size: u64,
+        force: bool,
+    ) -> Result<Vec<WalRecord>, Error> {
+        if force {
+            return Self::replay(fs, path);
+        }
+
+        let mut reader = fs.open_read(path)?;
+        let mut records = Vec::new();
+        let mut offset: u64 = 0;
+        let mut record = Vec::new();
+
+        let limit_exceeded = |records: usize, offset: u64| {
+            Error::new(
+                ErrorKind::FileTooLarge,
+                StorageError::ReplayLimitExceeded(records, offset, max_replay_size),
+            )
+        };
+        let truncated = |offset: u64| {
+            Error::new(
+                ErrorKind::InvalidData,
+                StorageError::TruncatedRecord(offset),
+            )
+        };
+
+        loop {
+            record.clear();
+
+            // [type: u8][k_len: u32]
+            let header_len = read_up_to(&mut reader, &mut record, 5)?;
+            if header_len == 0 {
+                break;
+            }
+            // No valid record starts with zeros (a key is at least 9 bytes), so this is either the
+            // zeroed tail of a preallocated log or corruption
+            if is_zero_tail(&record) {
+                if rest_is_zero(&mut reader)? {
+                    break;
+                }
+                return Err(truncated(offset));
+            }
+            if header_len < 5 {
+                return Err(truncated(offset));
+            }
+
+            let k_len = u32::from_be_bytes(record[1..5].try_into().unwrap()) as u64;
+            // An encoded key is the user key plus an 8 byte sequence and a type byte
+            if k_len < 9 || k_len > max_key_size as u64 + 9 {
+                return Err(truncated(offset));
+            }
+            if offset + 5 + k_len + 4 > max_replay_size {
+                return Err(limit_exceeded(records.len(), offset));
+            }
+            if read_up_to(&mut reader, &mut record, k_len + 4)? < k_len + 4 {
+                return Err(truncated(offset));
+            }
+
+            let v_start = record.len() - 4;
+            let v_len = u32::from_be_bytes(record[v_start..].try_into().unwrap()) as u64;
+            if v_len > max_value_size as u64 {
+                return Err(truncated(offset));
+            }
+            let record_len = 5 + k_len + 4 + v_len + 4;
+            if offset + record_len > max_replay_size {
+                return Err(limit_exceeded(records.len(), offset));
+            }
+            if read_up_to(&mut reader, &mut record, v_len + 4)? < v_len + 4 {
+                return Err(truncated(offset));
+            }
+
+            let (decoded, _) = decode_record(&record)
+                .map_err(|e| Error::new(ErrorKind::InvalidData, e.at_offset(offset)))?;
+            records.push(decoded);
+            offset += record_len;
+        }
+
+        Ok(records)
+    }
+
+    // Salvages the records before the first corruption and moves the damaged log aside with a
+    // .corrupt suffix so it can be inspected later. A clean log is left where it is.
+    pub fn repair(fs: &dyn FileSystem, path: &Path) -> Result<WalSalvage, Error> {
+        let data = fs.read(path)?;
+        let mut records = Vec::new();
+        let mut offset = 0;
+        let mut corruption_offset = None;
+
+        while offset < data.len() {
+            match decode_record(&data[offset..]) {
+                Ok((record, len)) => {
+                    records.push(record);
+                    offset += len;
+                }
+                Err(_) if is_zero_tail(&data[offset..]) => break,
+                Err(_) => {
+                    corruption_offset = Some(offset as u64);
+                    break;
+                }
+            }
+        }
+
+        let mut corrupt_path = None;
+        if corruption_offset.is_some() {
+            let mut name = path.as_os_str().to_owned();
+            name.push(".corrupt");
+            let target = PathBuf::from(name);
+
+            fs.rename(path, &target)?;
+            if let Some(parent) = path.parent() {
+                fs.sync_dir(parent)?;
+            }
+            corrupt_path = Some(target);
+        }
+
+        Ok(WalSalvage {
+            records,
+            corruption_offset,
+            corrupt_path,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct WalSalvage {
+    pub records: Vec<WalRecord>,
+    // Byte offset of the first record that could not be decoded
+    pub corruption_offset: Option<u64>,
+    // Where the damaged log was moved to, if it was corrupt
+    pub corrupt_path: Option<PathBuf>,
+}
+
+// replay rejects a record whose key type doesn't match its entry type, so one bad write would make
+// the whole log unreplayable
+fn check_key_type(entry_type: EntryType, key: &InternalKey) -> Result<(), Error> {
+    let expected = match entry_type {
+        EntryType::Put => KeyType::Put,
+        EntryType::Delete => KeyType::Delete,
+    };
+    if key.key_type != expected {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "WAL entry type {:?} does not match key type {:?}",
+                entry_type, key.key_type
+            ),
+        ));
+    }
+    Ok(())
+}
+
+// Appends up to len bytes to buf, returning how many were read before EOF
+fn read_up_to(reader: &mut dyn Read, buf: &mut Vec<u8>, len: u64) -> Result<u64, Error> {
+    reader.take(len).read_to_end(buf).map(|n| n as u64)
+}
+
+fn rest_is_zero(reader: &mut dyn Read) -> Result<bool, Error> {
+    let mut chunk = [0u8; 8192];
+    loop {
+        let n = retry_interrupted(|| reader.read(&mut chunk))?;
+        if n == 0 {
+            return Ok(true);
+        }
+        if !is_zero_tail(&chunk[..n]) {
+            return Ok(false);
+        }
+    }
+}
+
+fn is_zero_tail(data: &[u8]) -> bool {
+    data.iter().all(|b| *b == 0)
+}
+
+// Length of the prefix of data made up of whole, valid records. Only an all-zero remainder marks
+// the logical end; anything else is corruption that writes must not be positioned over.
+fn logical_len(data: &[u8]) -> Result<usize, StorageError> {
+    let mut offset = 0;
+    while offset < data.len() {
+        match decode_record(&data[offset..]) {
+            Ok((_, len)) => offset += len,
+            Err(_) if is_zero_tail(&data[offset..]) => break,
+            Err(e) => return Err(e.at_offset(offset as u64)),
+        }
+    }
+    Ok(offset)
+}
+
+// Record layout: [type: u8][k_len: u32][key][v_len: u32][value][crc: u32], all big-endian, with
+// the crc covering everything before it. Returns the record and the number of bytes it used.
+fn decode_record(data: &[u8]) -> Result<(WalRecord, usize), StorageError> {
+    let read_u32 = |at: usize| -> Result<u32, StorageError> {
+        let bytes: [u8; 4] = data
+            .get(at..at + 4)
+            .and_then(|b| b.try_into().ok())
+            .ok_or(StorageError::TruncatedRecord(0))?;
+        Ok(u32::from_be_bytes(bytes))
+    };
+
+    let entry_type = EntryType::try_from(*data.first().ok_or(StorageError::TruncatedRecord(0))?)?;
+
+    let k_len = read_u32(1)? as usize;
+    let k_start = 5;
+    let k_end = k_start + k_len;
+    let v_len = read_u32(k_end)? as usize;
+    let v_start = k_end + 4;
+    let v_end = v_start + v_len;
+    let crc = read_u32(v_end)?;
+    let record_len = v_end + 4;
+
+    let mut hasher = Hasher::new();
+    hasher.update(&data[..v_end]);
+    if hasher.finalize() != crc {
+        return Err(StorageError::ChecksumMismatch(0));
+    }
+
+    let key = InternalKey::decode(&data[k_start..k_end])?;
+    let record = match (entry_type, key.key_type) {
+        (EntryType::Put, KeyType::Put) => WalRecord::Put(key, data[v_start..v_end].to_vec()),
+        (EntryType::Delete, KeyType::Delete) if v_len == 0 => WalRecord::Delete(key),
+        _ => {
+            return Err(StorageError::DecodeError(format!(
+                "WAL entry type {:?} does not match key type {:?}",
+                entry_type, key.key_type
+            )));
+        }
+    };
+
+    Ok((record, record_len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memtable::MemTable;
+    use crate::vfs::InMemoryFs;
+    use tempfile::TempDir;
+
+    fn make_key(s: &str, seq: u64) -> InternalKey {
+        InternalKey::new(s.as_bytes().to_vec(), seq, KeyType::Put)
+    }
+
+    fn make_tombstone(s: &str, seq: u64) -> InternalKey {
+        InternalKey::new(s.as_bytes().to_vec(), seq, KeyType::Delete)
+    }
+
+    #[test]
+    fn test_write_through_in_memory_fs() {
+        let fs = InMemoryFs::new();
+        let dir = std::path::Path::new("/db");
+        fs.create_dir_all(dir).unwrap();
+
+        let path = dir.join("000002.log");
+        let mut wal = WriteAheadLog::open(Arc::new(fs.clone()), path.clone(), 16, 32).unwrap();
+
+        wal.write_put(&make_key("key", 1), b"value").unwrap();
+        wal.write_delete(&make_tombstone("key", 2)).unwrap();
+
+        assert_eq!(fs.read(&path).unwrap().len() as u64, wal.bytes_written);
+    }
+
+    #[test]
+    fn test_writes_update_metrics() {
+        let fs = InMemoryFs::new();
+        fs.create_dir_all(Path::new("/db")).unwrap();
+
+        let metrics = Arc::new(Metrics::new());
+        let mut wal =
+            WriteAheadLog::open(Arc::new(fs), PathBuf::from("/db/000002.log"), 16, 32).unwrap();
+        wal.set_metrics(Arc::clone(&metrics));
+
+        wal.write_put(&make_key("key", 1), b"value").unwrap();
+        wal.write_delete(&make_tombstone("key", 2)).unwrap();
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.counter("wal.records"), 2);
+        assert_eq!(snapshot.counter("wal.bytes_written"), wal.bytes_written);
+        assert_eq!(
+            snapshot.histogram("wal.sync_latency_us").unwrap().count(),
+            2
+        );
+    }
+
+    // Locks the on-disk record layout, including big-endian lengths and the crc position
+    #[test]
+    fn test_record_canonical_bytes() {
+        let fs = InMemoryFs::new();
+        fs.create_dir_all(Path::new("/db")).unwrap();
+        let path = PathBuf::from("/db/000002.log");
+        let mut wal = WriteAheadLog::open(Arc::new(fs.clone()), path.clone(), 16, 32).unwrap();
+
+        wal.write_put(&make_key("key", 1), b"value").unwrap();
+        let put_len = wal.bytes_written as usize;
+        wal.write_delete(&InternalKey::new(b"key".to_vec(), 2, KeyType::Delete))
+            .unwrap();
+
+        let bytes = fs.read(&path).unwrap();
+
+        let mut expected_put = vec![0x00, 0x00, 0x00, 0x00, 0x0c];
+        expected_put.extend_from_slice(b"key");
+        expected_put.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1, 0x01]);
+        expected_put.extend_from_slice(&[0x00, 0x00, 0x00, 0x05]);
+        expected_put.extend_from_slice(b"value");
+        expected_put.extend_from_slice(&[0x70, 0x1c, 0x7e, 0x03]);
+        assert_eq!(&bytes[..put_len], expected_put.as_slice());
+
+        let mut expected_delete = vec![0x01, 0x00, 0x00, 0x00, 0x0c];
+        expected_delete.extend_from_slice(b"key");
+        expected_delete.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 2, 0x00]);
+        expected_delete.extend_from_slice(&[0x00, 0x00, 0x00, 0x00]);
+        expected_delete.extend_from_slice(&[0x50, 0x14, 0x9e, 0x18]);
+        assert_eq!(&bytes[put_len..], expected_delete.as_slice());
+    }
+
+    #[test]
+    fn test_random_records_round_trip() {
+        use rand::Rng;
+
+        let fs = InMemoryFs::new();
+        fs.create_dir_all(Path::new("/db")).unwrap();
+        let path = PathBuf::from("/db/000002.log");
+        let mut wal = WriteAheadLog::open(Arc::new(fs.clone()), path.clone(), 64, 256).unwrap();
+
+        let mut rng = rand::rng();
+        let mut expected = Vec::new();
+        for seq in 0..500 {
+            let key_len = rng.random_range(0..=64);
+            let user_key: Vec<u8> = (0..key_len).map(|_| rng.random()).collect();
+
+            if rng.random_bool(0.8) {
+                let value_len = rng.random_range(0..=256);
+                let value: Vec<u8> = (0..value_len).map(|_| rng.random()).collect();
+                let key = InternalKey::new(user_key, seq, KeyType::Put);
+                wal.write_put(&key, &value).unwrap();
+                expected.push(WalRecord::Put(key, value));
+            } else {
+                let key = InternalKey::new(user_key, seq, KeyType::Delete);
+                wal.write_delete(&key).unwrap();
+                expected.push(WalRecord::Delete(key));
+            }
+        }
+
+        assert_eq!(WriteAheadLog::replay(&fs, &path).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_entry_buffer_is_reused_between_writes() {
+        let fs = InMemoryFs::new();
+        fs.create_dir_all(Path::new("/db")).unwrap();
+        let mut wal =
+            WriteAheadLog::open(Arc::new(fs), PathBuf::from("/db/000002.log"), 16, 64).unwrap();
+
+        wal.write_put(&make_key("key", 1), &[1u8; 64]).unwrap();
+        let buf_ptr = wal.entry_buf.as_ptr();
+        let capacity = wal.entry_buf.capacity();
+
+        for seq in 2..100 {
+            wal.write_put(&make_key("key", seq), &[2u8; 32]).unwrap();
+            wal.write_delete(&make_tombstone("key", seq)).unwrap();
+        }
+
+        assert_eq!(wal.entry_buf.as_ptr(), buf_ptr);
+        assert_eq!(wal.entry_buf.capacity(), capacity);
+    }
+
+    #[test]
+    fn test_large_entry_buffer_is_not_retained() {
+        let fs = InMemoryFs::new();
+        fs.create_dir_all(Path::new("/db")).unwrap();
+        let big = MAX_RETAINED_ENTRY_BUFFER * 2;
+        let mut wal =
+            WriteAheadLog::open(Arc::new(fs), PathBuf::from("/db/000002.log"), 16, big).unwrap();
+
+        wal.write_put(&make_key("key", 1), &vec![0u8; big]).unwrap();
+
+        assert!(wal.entry_buf.capacity() <= MAX_RETAINED_ENTRY_BUFFER);
+    }
+
+    #[test]
+    fn test_checked_len_within_u32() {
+        assert_eq!(checked_len(0).unwrap(), 0);
+        assert_eq!(checked_len(u32::MAX as usize).unwrap(), u32::MAX);
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn test_checked_len_rejects_overflow() {
+        let err = checked_len(u32::MAX as usize + 1).expect_err("length should not wrap");
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+
+        assert!(checked_len(usize::MAX).is_err());
+    }
+
+    #[test]
+    fn test_oversized_value_rejected_before_write() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("000002.log");
+        let mut wal = WriteAheadLog::new_with_limits(path.clone(), 16, 32).unwrap();
+
+        let err = wal
+            .write_put(&make_key("key", 1), &[7u8; 33])
+            .expect_err("value over the limit should be rejected");
+
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        assert_eq!(wal.bytes_written, 0);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_oversized_key_rejected_before_write() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("000002.log");
+        let mut wal = WriteAheadLog::new_with_limits(path.clone(), 4, 32).unwrap();
+
+        assert!(wal.write_put(&make_key("toolong", 1), b"v").is_err());
+        assert!(wal.write_delete(&make_tombstone("toolong", 2)).is_err());
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_new_with_config_takes_limits_from_config() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("000002.log");
+        let config = TaurusConfig::default();
+        let mut wal = WriteAheadLog::new_with_config(path.clone(), &config).unwrap();
+
+        let at_limit = "k".repeat(config.max_key_size() as usize);
+        let over_limit = "k".repeat(config.max_key_size() as usize + 1);
+
+        let err = wal.write_put(&make_key(&over_limit, 1), b"v").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        assert_eq!(wal.bytes_written, 0);
+
+        wal.write_put(&make_key(&at_limit, 2), b"v").unwrap();
+        assert!(wal.bytes_written > 0);
+    }
+
+    #[test]
+    fn test_values_at_the_limit_are_written() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("000002.log");
+        let mut wal = WriteAheadLog::new_with_limits(path.clone(), 4, 32).unwrap();
+
+        wal.write_put(&make_key("four", 1), &[7u8; 32]).unwrap();
+
+        assert!(wal.bytes_written > 0);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), wal.bytes_written);
+    }
+
+    #[test]
+    fn test_replay_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("000002.log");
+        let mut wal = WriteAheadLog::new(path.clone()).unwrap();
+
+        let deleted = InternalKey::new(b"key".to_vec(), 2, KeyType::Delete);
+        wal.write_put(&make_key("key", 1), b"value").unwrap();
+        wal.write_delete(&deleted).unwrap();
+
+        let records = WriteAheadLog::replay(&StdFs, &path).unwrap();
+
+        assert_eq!(
+            records,
+            vec![
+                WalRecord::Put(make_key("key", 1), b"value".to_vec()),
+                WalRecord::Delete(deleted),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_empty_value_put_replays_as_live_key() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("000002.log");
+        let mut wal = WriteAheadLog::new(path.clone()).unwrap();
+
+        wal.write_put(&make_key("empty", 1), &[]).unwrap();
+        wal.write_delete(&InternalKey::new(b"gone".to_vec(), 2, KeyType::Delete))
+            .unwrap();
+        drop(wal);
+
+        let records = WriteAheadLog::replay(&StdFs, &path).unwrap();
+        assert_eq!(records[0], WalRecord::Put(make_key("empty", 1), Vec::new()));
+        assert!(matches!(records[1], WalRecord::Delete(_)));
+
+        let mut memtable = MemTable::new(1024 * 1024);
+        for record in records {
+            if let WalRecord::Put(key, value) = record {
+                memtable.put(key, value).unwrap();
+            }
+        }
+        assert_eq!(memtable.get(&make_key("empty", 1)), Some(vec![]));
+    }
+
+    #[test]
+    fn test_duplicate_internal_key_replays_last_wins() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("000002.log");
+        let mut wal = WriteAheadLog::new(path.clone()).unwrap();
+
+        wal.write_put(&make_key("key", 5), b"first").unwrap();
+        wal.write_put(&make_key("other", 6), b"other").unwrap();
+        wal.write_put(&make_key("key", 5), b"second").unwrap();
+        drop(wal);
+
+        let records = WriteAheadLog::replay(&StdFs, &path).unwrap();
+        let mut memtable = MemTable::new(1024 * 1024);
+        let duplicates = memtable.replay(records).unwrap();
+
+        assert_eq!(duplicates, 1);
+        assert_eq!(memtable.get(&make_key("key", 5)), Some(b"second".to_vec()));
+        assert_eq!(memtable.iter().count(), 2);
+
+        let mut expected = MemTable::new(1024 * 1024);
+        expected
+            .put(make_key("key", 5), b"second".to_vec())
+            .unwrap();
+        expected
+            .put(make_key("other", 6), b"other".to_vec())
+            .unwrap();
+        assert_eq!(memtable.size(), expected.size());
+    }
+
+    #[test]
+    fn test_replay_into_memtable_keeps_tombstones() {
+        let mut memtable = MemTable::new(1024 * 1024);
+        let tombstone = InternalKey::new(b"key".to_vec(), 2, KeyType::Delete);
+
+        let duplicates = memtable
+            .replay(vec![
+                WalRecord::Put(make_key("key", 1), b"value".to_vec()),
+                WalRecord::Delete(tombstone.clone()),
+            ])
+            .unwrap();
+
+        assert_eq!(duplicates, 0);
+        assert_eq!(memtable.get(&tombstone), Some(Vec::new()));
+        assert_eq!(memtable.get(&make_key("key", 1)), Some(b"value".to_vec()));
+    }
+
+    #[test]
+    fn test_replay_limit_aborts_and_reports_progress() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("000002.log");
+        let mut wal = WriteAheadLog::new(path.clone()).unwrap();
+
+        wal.write_put(&make_key("a", 1), b"one").unwrap();
+        wal.write_put(&make_key("b", 2), b"two").unwrap();
+        let two_records = wal.bytes_written;
+        wal.write_put(&make_key("c", 3), b"three").unwrap();
+        let total = wal.bytes_written;
+        drop(wal);
+
+        let err =
+            WriteAheadLog::replay_with_limit(&StdFs, &path, 16, 32, total - 1, false).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::FileTooLarge);
+        assert!(
+            err.to_string()
+                .contains(&format!("replayed 2 records ({two_records} bytes)")),
+            "{err}"
+        );
+
+        let records =
+            WriteAheadLog::replay_with_limit(&StdFs, &path, 16, 32, total, false).unwrap();
+        assert_eq!(records.len(), 3);
+    }
+
+    #[test]
+    fn test_replay_limit_trips_before_reading_whole_log() {
+        let fs = InMemoryFs::new();
+        fs.create_dir_all(Path::new("/db")).unwrap();
+        let path = PathBuf::from("/db/000002.log");
+        let big = 4 * 1024 * 1024;
+        let mut wal = WriteAheadLog::open(Arc::new(fs.clone()), path.clone(), 16, big).unwrap();
+
+        wal.write_put(&make_key("a", 1), b"one").unwrap();
+        let first_len = wal.bytes_written;
+        wal.write_put(&make_key("b", 2), &vec![7u8; big]).unwrap();
+        let total = wal.bytes_written;
+        drop(wal);
+
+        let before = fs.bytes_read();
+        let err =
+            WriteAheadLog::replay_with_limit(&fs, &path, 16, big, 64 * 1024, false).unwrap_err();
+        let read = fs.bytes_read() - before;
+
+        assert_eq!(err.kind(), ErrorKind::FileTooLarge);
+        assert!(
+            err.to_string()
+                .contains(&format!("replayed 1 records ({first_len} bytes)")),
+            "{err}"
+        );
+        assert!(read < 1024, "read {read} of {total} bytes");
+    }
+
+    #[test]
+    fn test_replay_limit_handles_zero_tail_and_corruption() {
+        let fs = InMemoryFs::new();
+        fs.create_dir_all(Path::new("/db")).unwrap();
+        let path = PathBuf::from("/db/000002.log");
+
+        let mut wal =
+            WriteAheadLog::open_preallocated(Arc::new(fs.clone()), path.clone(), 16, 32, 4096)
+                .unwrap();
+        wal.write_put(&make_key("a", 1), b"1").unwrap();
+        let first_len = wal.bytes_written as usize;
+        wal.write_put(&make_key("b", 2), b"2").unwrap();
+        drop(wal);
+
+        let records = WriteAheadLog::replay_with_limit(&fs, &path, 16, 32, 4096, false).unwrap();
+        assert_eq!(records, WriteAheadLog::replay(&fs, &path).unwrap());
+        assert_eq!(records.len(), 2);
+
+        let mut bytes = fs.read(&path).unwrap();
+        bytes[first_len - 1] ^= 0xff;
+        fs.create(&path).unwrap().write_all(&bytes).unwrap();
+
+        let err = WriteAheadLog::replay_with_limit(&fs, &path, 16, 32, 4096, false).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_replay_limit_reports_corrupt_lengths_as_corruption() {
+        let fs = InMemoryFs::new();
+        fs.create_dir_all(Path::new("/db")).unwrap();
+        let path = PathBuf::from("/db/000002.log");
+
+        let mut wal = WriteAheadLog::open(Arc::new(fs.clone()), path.clone(), 16, 32).unwrap();
+        wal.write_put(&make_key("a", 1), b"one").unwrap();
+        let first_len = wal.bytes_written as usize;
+        wal.write_put(&make_key("b", 2), b"two").unwrap();
+        drop(wal);
+        let clean = fs.read(&path).unwrap();
+        let key_len = make_key("b", 2).encoded_len();
+
+        // High byte of the second record's k_len, then its v_len, then a k_len below the minimum
+        let v_len_at = first_len + 5 + key_len;
+        for (at, byte) in [
+            (first_len + 1, 0x80),
+            (v_len_at, 0x80),
+            (first_len + 4, 0x01),
+        ] {
+            let mut bytes = clean.clone();
+            bytes[at] = byte;
+            fs.create(&path).unwrap().write_all(&bytes).unwrap();
+
+            let err = WriteAheadLog::replay_with_limit(&fs, &path, 16, 32, 64, false).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidData, "byte {at}: {err}");
+            assert!(
+                err.to_string()
+                    .contains(&StorageError::TruncatedRecord(first_len as u64).to_string()),
+                "{err}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_replay_limit_force_overrides() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("000002.log");
+        let mut wal = WriteAheadLog::new(path.clone()).unwrap();
+
+        wal.write_put(&make_key("a", 1), b"one").unwrap();
+        wal.write_put(&make_key("b", 2), b"two").unwrap();
+        drop(wal);
+
+        assert!(WriteAheadLog::replay_with_limit(&StdFs, &path, 16, 32, 1, false).is_err());
+
+        let records = WriteAheadLog::replay_with_limit(&StdFs, &path, 16, 32, 1, true).unwrap();
+        assert_eq!(records.len(), 2);
+    }
+
+    #[test]
+    fn test_mismatched_key_type_rejected_before_write() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("000002.log");
+        let mut wal = WriteAheadLog::new(path.clone()).unwrap();
+
+        // A put record must carry a Put key, otherwise an empty put could be read as a tombstone
+        let err = wal.write_put(&make_tombstone("k", 1), &[]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+
+        let err = wal.write_delete(&make_key("k", 2)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+
+        assert_eq!(wal.bytes_written, 0);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 0);
+
+        wal.write_put(&make_key("k", 3), b"v").unwrap();
+        assert_eq!(WriteAheadLog::replay(&StdFs, &path).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_replay_detects_corruption() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("000002.log");
+        let mut wal = WriteAheadLog::new(path.clone()).unwrap();
+
+        wal.write_put(&make_key("key", 1), b"value").unwrap();
+        let mut bytes = std::fs::read(&path).unwrap();
+
+        let last = bytes.len() - 5;
+        bytes[last] ^= 0xff;
+        std::fs::write(&path, &bytes).unwrap();
+        assert_eq!(
+            WriteAheadLog::replay(&StdFs, &path).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+
+        bytes.truncate(bytes.len() - 3);
+        std::fs::write(&path, &bytes).unwrap();
+        assert_eq!(
+            WriteAheadLog::replay(&StdFs, &path).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn test_repair_salvages_records_before_truncation() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("000002.log");
+        let mut wal = WriteAheadLog::new(path.clone()).unwrap();
+
+        wal.write_put(&make_key("a", 1), b"one").unwrap();
+        wal.write_put(&make_key("b", 2), b"two").unwrap();
+        let good_len = wal.bytes_written;
+        wal.write_put(&make_key("c", 3), b"three").unwrap();
+        drop(wal);
+
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes.truncate(bytes.len() - 6);
+        std::fs::write(&path, &bytes).unwrap();
+
+        let salvage = WriteAheadLog::repair(&StdFs, &path).unwrap();
+
+        assert_eq!(
+            salvage.records,
+            vec![
+                WalRecord::Put(make_key("a", 1), b"one".to_vec()),
+                WalRecord::Put(make_key("b", 2), b"two".to_vec()),
+            ]
+        );
+        assert_eq!(salvage.corruption_offset, Some(good_len));
+
+        let corrupt_path = temp_dir.path().join("000002.log.corrupt");
+        assert_eq!(
+            salvage.corrupt_path.as_deref(),
+            Some(corrupt_path.as_path())
+        );
+        assert!(!path.exists());
+        assert_eq!(std::fs::read(&corrupt_path).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_repair_stops_at_bad_crc_in_the_middle() {
+        let fs = InMemoryFs::new();
+        fs.create_dir_all(Path::new("/db")).unwrap();
+        let path = PathBuf::from("/db/000002.log");
+        let mut wal = WriteAheadLog::open(Arc::new(fs.clone()), path.clone(), 16, 32).unwrap();
+
+        wal.write_put(&make_key("a", 1), b"one").unwrap();
+        let first_len = wal.bytes_written;
+        wal.write_put(&make_key("b", 2), b"two").unwrap();
+        let second_len = wal.bytes_written;
+        wal.write_put(&make_key("c", 3), b"three").unwrap();
+        drop(wal);
+
+        let mut bytes = fs.read(&path).unwrap();
+        bytes[second_len as usize - 1] ^= 0xff;
+        let mut file = fs.create(&path).unwrap();
+        file.write_all(&bytes).unwrap();
+
+        let salvage = WriteAheadLog::repair(&fs, &path).unwrap();
+
+        assert_eq!(salvage.records.len(), 1);
+        assert_eq!(salvage.corruption_offset, Some(first_len));
+        assert!(!fs.exists(&path));
+        assert!(fs.exists(Path::new("/db/000002.log.corrupt")));
+    }
+
+    #[test]
+    fn test_repair_leaves_clean_log_in_place() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("000002.log");
+        let mut wal = WriteAheadLog::new(path.clone()).unwrap();
+        wal.write_put(&make_key("a", 1), b"one").unwrap();
+        drop(wal);
+
+        let salvage = WriteAheadLog::repair(&StdFs, &path).unwrap();
+
+        assert_eq!(salvage.records.len(), 1);
+        assert_eq!(salvage.corruption_offset, None);
+        assert_eq!(salvage.corrupt_path, None);
+        assert!(path.exists());
+    }
+
+    #[test]
+    fn test_preallocated_wal_replays_only_written_records() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("000002.log");
+        let mut wal =
+            WriteAheadLog::open_preallocated(Arc::new(StdFs), path.clone(), 16, 32, 64 * 1024)
+                .unwrap();
+
+        wal.write_put(&make_key("a", 1), b"1").unwrap();
+        wal.write_put(&make_key("b", 2), b"2").unwrap();
+
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 64 * 1024);
+
+        let records = WriteAheadLog::replay(&StdFs, &path).unwrap();
+        assert_eq!(
+            records,
+            vec![
+                WalRecord::Put(make_key("a", 1), b"1".to_vec()),
+                WalRecord::Put(make_key("b", 2), b"2".to_vec()),
+            ]
+        );
+
+        let written = wal.bytes_written;
+        wal.close().unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), written);
+    }
+
+    #[test]
+    fn test_preallocated_wal_reopen_continues_after_last_record() {
+        let fs = InMemoryFs::new();
+        let dir = std::path::Path::new("/db");
+        fs.create_dir_all(dir).unwrap();
+        let path = dir.join("000002.log");
+
+        let mut wal =
+            WriteAheadLog::open_preallocated(Arc::new(fs.clone()), path.clone(), 16, 32, 4096)
+                .unwrap();
+        wal.write_put(&make_key("a", 1), b"1").unwrap();
+        drop(wal);
+
+        let mut wal =
+            WriteAheadLog::open_preallocated(Arc::new(fs.clone()), path.clone(), 16, 32, 4096)
+                .unwrap();
+        wal.write_put(&make_key("b", 2), b"2").unwrap();
+
+        assert_eq!(fs.read(&path).unwrap().len(), 4096);
+        assert_eq!(WriteAheadLog::replay(&fs, &path).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_preallocated_reopen_rejects_corruption_before_valid_records() {
+        let fs = InMemoryFs::new();
+        fs.create_dir_all(Path::new("/db")).unwrap();
+        let path = PathBuf::from("/db/000002.log");
+
+        let mut wal =
+            WriteAheadLog::open_preallocated(Arc::new(fs.clone()), path.clone(), 16, 32, 4096)
+                .unwrap();
+        wal.write_put(&make_key("a", 1), b"1").unwrap();
+        wal.write_put(&make_key("b", 2), b"2").unwrap();
+        let b_end = wal.bytes_written as usize;
+        wal.write_put(&make_key("c", 3), b"3").unwrap();
+        wal.write_put(&make_key("d", 4), b"4").unwrap();
+        drop(wal);
+
+        let mut bytes = fs.read(&path).unwrap();
+        bytes[b_end - 1] ^= 0xff;
+        fs.create(&path).unwrap().write_all(&bytes).unwrap();
+
+        let err =
+            WriteAheadLog::open_preallocated(Arc::new(fs.clone()), path.clone(), 16, 32, 4096)
+                .unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(fs.read(&path).unwrap(), bytes, "Nothing should be written");
+
+        // repair still salvages the records before the corruption
+        let salvage = WriteAheadLog::repair(&fs, &path).unwrap();
+        assert_eq!(salvage.records.len(), 1);
     }
 }